@@ -0,0 +1,268 @@
+use crate::{
+    codegen::{
+        Encoding, Sidecars, identifier, include_guard, sidecar_path, write_bytes, write_embed,
+    },
+    error::Error,
+    generate_script, man,
+    shell::CompletionShell,
+};
+use clap::Command;
+use std::{io::Write, path::Path};
+
+/// Writes `entries` as `static const unsigned char` arrays followed by a lookup function
+/// `const unsigned char *<function>(const char *name, size_t *len)` returning NULL for unknown names.
+///
+/// With the `embed` encoding the arrays are filled by `#embed` and the sidecar files are returned.
+fn write_lookup(
+    source: &mut Vec<u8>,
+    function: &str,
+    entries: &[(String, Vec<u8>)],
+    encoding: Encoding,
+    header_path: &Path,
+) -> Sidecars {
+    let mut sidecars = Vec::new();
+    for (key, buf) in entries {
+        writeln!(
+            source,
+            "static const unsigned char {function}_{}[] = {{",
+            identifier(key)
+        )
+        .unwrap();
+        if encoding == Encoding::Embed {
+            let path = sidecar_path(header_path, key);
+            write_embed(source, &path);
+            sidecars.push((path, buf.clone()));
+        } else {
+            write_bytes(source, buf);
+        }
+        // Empty initializer lists are not valid C99.
+        if buf.is_empty() {
+            writeln!(source, "    0x00,").unwrap();
+        }
+        writeln!(source, "}};").unwrap();
+        writeln!(source).unwrap();
+    }
+
+    writeln!(
+        source,
+        "const unsigned char *{function}(const char *name, size_t *len) {{"
+    )
+    .unwrap();
+    for (key, _) in entries {
+        let ident = identifier(key);
+        writeln!(source, "    if (strcmp(name, \"{key}\") == 0) {{").unwrap();
+        writeln!(
+            source,
+            "        if (len) *len = {};",
+            length_constant(function, key)
+        )
+        .unwrap();
+        writeln!(source, "        return {function}_{ident};").unwrap();
+        writeln!(source, "    }}").unwrap();
+    }
+    writeln!(source, "    if (len) *len = 0;").unwrap();
+    writeln!(source, "    return NULL;").unwrap();
+    writeln!(source, "}}").unwrap();
+    sidecars
+}
+
+fn length_constant(function: &str, key: &str) -> String {
+    format!("{}_{}_LEN", function, identifier(key)).to_ascii_uppercase()
+}
+
+/// Generates a C99 header and source pair embedding the completion scripts for `shells`, and
+/// optionally the man pages, for projects that cannot use the C++ header.
+///
+/// The header declares a length constant per embedded file and the lookup functions; the source
+/// holds the data and includes the header by the file name of `header_path`. Sidecar files for the
+/// `embed` encoding are returned alongside. Only the `hex` and `embed` encodings are supported.
+pub fn generate_c_sources(
+    command: &Command,
+    shells: &[CompletionShell],
+    embed_man: bool,
+    encoding: Encoding,
+    include_guard_kind: &str,
+    header_path: &Path,
+) -> Result<(Vec<u8>, Vec<u8>, Sidecars), Error> {
+    let scripts = shells
+        .iter()
+        .map(|shell| Ok((shell.to_string(), generate_script(*shell, command)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let man_pages = if embed_man {
+        man::generate_man_pages(command)
+    } else {
+        Vec::new()
+    };
+
+    let header_name = header_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .expect("output file name is checked by run");
+    let (guard_start, guard_end) = include_guard(include_guard_kind, header_name);
+
+    let mut header = Vec::new();
+    write!(header, "{guard_start}").unwrap();
+    writeln!(header, "#include <stddef.h>").unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "#ifdef __cplusplus").unwrap();
+    writeln!(header, "extern \"C\" {{").unwrap();
+    writeln!(header, "#endif").unwrap();
+    writeln!(header).unwrap();
+    writeln!(
+        header,
+        "#define CLAPPER_SHELLS {}",
+        shells
+            .iter()
+            .map(|s| format!("\"{}\"", s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+    .unwrap();
+    writeln!(header, "#define CLAPPER_SHELL_COUNT {}", shells.len()).unwrap();
+    for (key, buf) in &scripts {
+        writeln!(
+            header,
+            "#define {} {}",
+            length_constant("clapper_completion", key),
+            buf.len()
+        )
+        .unwrap();
+    }
+    writeln!(header).unwrap();
+    writeln!(
+        header,
+        "/* Returns the completion script for `shell` and stores its length in `len`, or NULL. */"
+    )
+    .unwrap();
+    writeln!(
+        header,
+        "const unsigned char *clapper_completion(const char *shell, size_t *len);"
+    )
+    .unwrap();
+    if embed_man {
+        writeln!(header).unwrap();
+        for (key, buf) in &man_pages {
+            writeln!(
+                header,
+                "#define {} {}",
+                length_constant("clapper_man_page", key),
+                buf.len()
+            )
+            .unwrap();
+        }
+        writeln!(header).unwrap();
+        writeln!(
+            header,
+            "/* Returns the man page `name` (e.g. \"{}\") and stores its length in `len`, or NULL. */",
+            man_pages[0].0
+        )
+        .unwrap();
+        writeln!(
+            header,
+            "const unsigned char *clapper_man_page(const char *name, size_t *len);"
+        )
+        .unwrap();
+    }
+    writeln!(header).unwrap();
+    writeln!(header, "#ifdef __cplusplus").unwrap();
+    writeln!(header, "}}").unwrap();
+    writeln!(header, "#endif").unwrap();
+    write!(header, "{guard_end}").unwrap();
+
+    let mut source = Vec::new();
+    writeln!(source, "#include \"{header_name}\"").unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "#include <string.h>").unwrap();
+    writeln!(source).unwrap();
+    let mut sidecars = write_lookup(
+        &mut source,
+        "clapper_completion",
+        &scripts,
+        encoding,
+        header_path,
+    );
+    if embed_man {
+        writeln!(source).unwrap();
+        sidecars.extend(write_lookup(
+            &mut source,
+            "clapper_man_page",
+            &man_pages,
+            encoding,
+            header_path,
+        ));
+    }
+
+    Ok((header, source, sidecars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::testing::{compile, run};
+
+    const MAIN: &str = r#"#include "completions.h"
+
+#include <stdio.h>
+
+int main(int argc, char **argv) {
+    size_t len = 0;
+    const unsigned char *data = argc > 2 ? clapper_man_page(argv[2], &len)
+                                         : clapper_completion(argv[1], &len);
+    if (!data) {
+        printf("none %u\n", (unsigned)len);
+        return 0;
+    }
+    fwrite(data, 1, len, stdout);
+    return 0;
+}
+"#;
+
+    #[test]
+    fn generated_sources_compile_and_look_up_files() {
+        let command = Command::new("t")
+            .about("t")
+            .subcommand(Command::new("run").about("r"));
+        let shells = [CompletionShell::Bash, CompletionShell::Fish];
+        let (header, source, sidecars) = generate_c_sources(
+            &command,
+            &shells,
+            true,
+            Encoding::Hex,
+            "macro",
+            Path::new("completions.h"),
+        )
+        .unwrap();
+        assert!(sidecars.is_empty());
+        let header = String::from_utf8(header).unwrap();
+        let source = String::from_utf8(source).unwrap();
+        let files = [
+            ("completions.h", header.as_str()),
+            ("completions.c", source.as_str()),
+            ("main.c", MAIN),
+        ];
+        let flags = [
+            "-std=c99",
+            "-pedantic",
+            "-Wall",
+            "-Wextra",
+            "-Werror",
+            "-o",
+            "main",
+            "main.c",
+            "completions.c",
+        ];
+        let Some(dir) = compile("gcc", "c-sources", &files, &flags) else {
+            return;
+        };
+
+        for shell in shells {
+            let script = String::from_utf8(generate_script(shell, &command).unwrap()).unwrap();
+            assert_eq!(run(&dir, "main", &[], &[&shell.to_string()]), script);
+        }
+        assert_eq!(run(&dir, "main", &[], &["zsh"]), "none 0\n");
+        let (name, page) = &man::generate_man_pages(&command)[0];
+        let page = String::from_utf8(page.clone()).unwrap();
+        assert_eq!(run(&dir, "main", &[], &["", name]), page);
+        assert_eq!(run(&dir, "main", &[], &["", "t.9"]), "none 0\n");
+    }
+}
@@ -0,0 +1,145 @@
+use crate::dynamic;
+use clap::{Arg, ArgAction, Command, ValueHint};
+use serde::Serialize;
+use serde_norway::{Mapping, Value};
+
+/// A command in the [carapace-spec](https://github.com/carapace-sh/carapace-spec) YAML format.
+#[derive(Serialize)]
+struct SpecCommand {
+    name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    flags: Mapping,
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    persistentflags: Mapping,
+    #[serde(skip_serializing_if = "SpecCompletion::is_empty")]
+    completion: SpecCompletion,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    commands: Vec<SpecCommand>,
+}
+
+#[derive(Serialize, Default)]
+struct SpecCompletion {
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    flag: Mapping,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    positional: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    positionalany: Vec<String>,
+}
+
+impl SpecCompletion {
+    fn is_empty(&self) -> bool {
+        self.flag.is_empty() && self.positional.is_empty() && self.positionalany.is_empty()
+    }
+}
+
+/// The flag definition key, e.g. `-o, --output=` for an option taking a value or `-v*` for a
+/// repeatable switch.
+fn flag_key(arg: &Arg) -> Option<String> {
+    let mut names = Vec::new();
+    names.extend(arg.get_short().map(|s| format!("-{s}")));
+    names.extend(arg.get_long().map(|l| format!("--{l}")));
+    if names.is_empty() {
+        return None;
+    }
+
+    let mut key = names.join(", ");
+    if matches!(arg.get_action(), ArgAction::Append | ArgAction::Count) {
+        key.push('*');
+    }
+    if arg.get_action().takes_values() {
+        key.push('=');
+    }
+    Some(key)
+}
+
+/// The name carapace uses to refer to a flag in the `completion` section.
+fn flag_name(arg: &Arg) -> String {
+    arg.get_long()
+        .map(str::to_string)
+        .or_else(|| arg.get_short().map(|s| s.to_string()))
+        .unwrap_or_else(|| arg.get_id().to_string())
+}
+
+fn value_completion(arg: &Arg) -> Vec<String> {
+    if let Some(command) = dynamic::arg_command(arg) {
+        return vec![dynamic::carapace_action(&command)];
+    }
+
+    let possible_values = arg.get_possible_values();
+    if !possible_values.is_empty() {
+        return possible_values
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| match v.get_help() {
+                Some(help) => format!("{}\t{}", v.get_name(), help),
+                None => v.get_name().to_string(),
+            })
+            .collect();
+    }
+
+    let action = match arg.get_value_hint() {
+        ValueHint::AnyPath | ValueHint::FilePath => "$files",
+        ValueHint::DirPath => "$directories",
+        ValueHint::ExecutablePath | ValueHint::CommandName => "$executables",
+        _ => return Vec::new(),
+    };
+    vec![action.to_string()]
+}
+
+fn make_spec(cmd: &Command) -> SpecCommand {
+    let mut spec = SpecCommand {
+        name: cmd.get_name().to_string(),
+        aliases: cmd.get_visible_aliases().map(str::to_string).collect(),
+        description: cmd.get_about().map(|a| a.to_string()).unwrap_or_default(),
+        flags: Mapping::new(),
+        persistentflags: Mapping::new(),
+        completion: SpecCompletion::default(),
+        commands: Vec::new(),
+    };
+
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        let completion = value_completion(arg);
+
+        if arg.is_positional() {
+            let variadic =
+                arg.get_num_args().is_some_and(|n| n.max_values() > 1) || arg.is_last_set();
+            if variadic {
+                spec.completion.positionalany = completion;
+            } else {
+                spec.completion.positional.push(completion);
+            }
+            continue;
+        }
+
+        let Some(key) = flag_key(arg) else { continue };
+        let help = Value::from(arg.get_help().map(|h| h.to_string()).unwrap_or_default());
+        if arg.is_global_set() {
+            spec.persistentflags.insert(Value::from(key), help);
+        } else {
+            spec.flags.insert(Value::from(key), help);
+        }
+
+        if !completion.is_empty() {
+            spec.completion
+                .flag
+                .insert(Value::from(flag_name(arg)), Value::from(completion));
+        }
+    }
+
+    spec.commands = cmd
+        .get_subcommands()
+        .filter(|s| !s.is_hide_set())
+        .map(make_spec)
+        .collect();
+    spec
+}
+
+/// Renders `command` as a carapace-spec YAML document.
+pub fn generate_carapace_spec(command: &Command) -> String {
+    serde_norway::to_string(&make_spec(command)).unwrap()
+}
@@ -0,0 +1,1021 @@
+use crate::{
+    ArgumentDef, CommandDef, NumArgs, OptionAction, PossibleValueDef, VALUE_TYPES, is_command_line,
+    parse_value_hint, try_value_range,
+};
+use clap::builder::ValueRange;
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The outputs would be wrong or clap would panic while generating them.
+    Error,
+    /// The outputs can be generated but are likely not what was meant.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A problem in the spec, located by the JSON path of the offending value, e.g.
+/// `$.command.subcommands[0].options[1].short`.
+#[derive(Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+/// Names visible in a command, mapped to the path defining them.
+#[derive(Clone, Default)]
+struct Scope {
+    /// Option ids and argument names.
+    ids: HashMap<String, String>,
+    /// `--long` and `-s` names including aliases, with the id of their option.
+    flags: HashMap<String, (String, String)>,
+}
+
+impl Scope {
+    /// Forgets the global `id` when the command defines its own argument with that id, which clap
+    /// then does not propagate.
+    fn shadow(&mut self, inherited: &Scope, id: &str) {
+        if inherited.ids.contains_key(id) && self.ids.get(id) == inherited.ids.get(id) {
+            self.ids.remove(id);
+            self.flags.retain(|_, (_, owner)| owner != id);
+        }
+    }
+}
+
+/// Whether `num_args` is given and allows more than one value.
+fn multiple(num_args: &Option<NumArgs>) -> bool {
+    num_args
+        .as_ref()
+        .and_then(|n| try_value_range(n).ok())
+        .is_some_and(|range| range.max_values() > 1)
+}
+
+/// The field making `argument` a trailing var arg, `trailing_var_arg` itself or its command line hint.
+fn trailing_field(argument: &ArgumentDef) -> &'static str {
+    match (argument.trailing_var_arg, &argument.value_hint) {
+        (true, _) => "trailing_var_arg",
+        (false, Some(_)) => "value_hint",
+        (false, None) => "value_type",
+    }
+}
+
+/// The number of values clap lets the positional `argument` take, `None` when `num_args` is invalid.
+fn positional_range(argument: &ArgumentDef) -> Option<ValueRange> {
+    match &argument.num_args {
+        Some(num_args) => try_value_range(num_args).ok(),
+        None if argument.trailing_var_arg
+            || is_command_line(&argument.value_hint, &argument.value_type) =>
+        {
+            Some(ValueRange::new(1..))
+        }
+        None => Some(ValueRange::SINGLE),
+    }
+}
+
+/// The field setting how many values `argument` takes, `num_args` or what defaults it.
+fn range_field(argument: &ArgumentDef) -> &'static str {
+    match argument.num_args {
+        Some(_) => "num_args",
+        None => trailing_field(argument),
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn error(&mut self, path: String, message: String) {
+        self.problems.push(Problem {
+            severity: Severity::Error,
+            path,
+            message,
+        });
+    }
+
+    fn warning(&mut self, path: String, message: String) {
+        self.problems.push(Problem {
+            severity: Severity::Warning,
+            path,
+            message,
+        });
+    }
+
+    fn name(&mut self, path: String, name: &str) {
+        if name.is_empty() {
+            self.error(path, "empty name".to_string());
+        }
+    }
+
+    fn description(&mut self, path: String, description: &str) {
+        if description.trim().is_empty() {
+            self.warning(path, "empty description".to_string());
+        }
+    }
+
+    /// Records `key` in `seen`, reporting it when it was already defined elsewhere.
+    fn unique(
+        &mut self,
+        seen: &mut HashMap<String, String>,
+        what: &str,
+        key: String,
+        path: String,
+    ) {
+        match seen.get(&key) {
+            Some(first) => self.error(
+                path,
+                format!("duplicate {what} '{key}', also used by {first}"),
+            ),
+            None => {
+                seen.insert(key, path);
+            }
+        }
+    }
+
+    /// Reports `id` naming itself in `field`, which clap rejects.
+    fn self_reference(&mut self, path: &str, field: &str, id: &str, names: &[String]) {
+        if let Some(i) = names.iter().position(|name| name == id) {
+            self.error(
+                format!("{path}.{field}[{i}]"),
+                format!("argument '{id}' cannot name itself in {field}"),
+            );
+        }
+    }
+
+    /// Checks that `required` is not combined with `global` or `required_unless_present`.
+    fn required(&mut self, path: &str, required: bool, global: bool, unless: &[String]) {
+        if !required {
+            return;
+        }
+        if global {
+            self.error(
+                format!("{path}.required"),
+                "global arguments cannot be required".to_string(),
+            );
+        }
+        if !unless.is_empty() {
+            self.error(
+                format!("{path}.required_unless_present"),
+                "required arguments cannot also be required_unless_present".to_string(),
+            );
+        }
+    }
+
+    fn references(&mut self, path: &str, field: &str, names: &[String], known: &[&str]) {
+        for (i, name) in names.iter().enumerate() {
+            if !known.contains(&name.as_str()) {
+                self.error(
+                    format!("{path}.{field}[{i}]"),
+                    format!("unknown argument or group '{name}'"),
+                );
+            }
+        }
+    }
+
+    /// Checks `value_type`, `value_hint`, `num_args` and that the possible values fit the type.
+    fn values(
+        &mut self,
+        path: &str,
+        value_type: &Option<String>,
+        value_hint: &Option<String>,
+        num_args: &Option<NumArgs>,
+        possible_values: &[PossibleValueDef],
+    ) {
+        if let Some(Err(e)) = value_hint.as_deref().map(parse_value_hint) {
+            self.error(format!("{path}.value_hint"), e);
+        }
+        if let Some(Err(e)) = num_args.as_ref().map(try_value_range) {
+            self.error(format!("{path}.num_args"), e);
+        }
+
+        let Some(value_type) = value_type.as_deref() else {
+            return;
+        };
+        if !VALUE_TYPES.contains(&value_type) {
+            self.warning(
+                format!("{path}.value_type"),
+                format!(
+                    "unknown value_type '{value_type}', expected one of {}",
+                    VALUE_TYPES.join(", ")
+                ),
+            );
+        }
+        let valid: fn(&str) -> bool = match value_type {
+            "boolean" => |v| v.parse::<bool>().is_ok(),
+            "integer" => |v| v.parse::<i64>().is_ok(),
+            "float" => |v| v.parse::<f64>().is_ok(),
+            _ => return,
+        };
+        for (i, value) in possible_values.iter().enumerate() {
+            if !valid(value.value()) {
+                self.warning(
+                    format!("{path}.possible_values[{i}]"),
+                    format!(
+                        "possible value '{}' is not a valid {value_type}",
+                        value.value()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Checks which positional arguments may take multiple values: the last one, or the second to
+    /// last one when the last is required or `last`, and only one of them a variable number.
+    fn multiple_positionals(&mut self, def: &CommandDef, path: &str) {
+        let Some((last, init)) = def.arguments.split_last() else {
+            return;
+        };
+        let ranges = def
+            .arguments
+            .iter()
+            .map(positional_range)
+            .collect::<Vec<_>>();
+        let multiple = |i: usize| ranges[i].as_ref().is_some_and(|r| r.max_values() > 1);
+        // Trailing var args before the last positional are reported already.
+        let Some(first) = (0..init.len()).find(|i| multiple(*i) && !init[*i].is_trailing_var_arg())
+        else {
+            return;
+        };
+
+        let second = init.len() - 1;
+        if !multiple(second) && !last.last {
+            self.error(
+                format!("{path}.arguments[{first}].{}", range_field(&init[first])),
+                "only the last or second to last positional argument may accept multiple values"
+                    .to_string(),
+            );
+        } else if !last.required && !last.last && !init[second].last {
+            self.error(
+                format!("{path}.arguments[{}].required", init.len()),
+                format!(
+                    "positional argument after {path}.arguments[{second}], which accepts \
+                     multiple values, must be required or last"
+                ),
+            );
+        }
+
+        let variable = ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                r.as_ref()
+                    .is_some_and(|r| r.max_values() > 1 && r.min_values() != r.max_values())
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let last_pair =
+            last.last && multiple(init.len()) && multiple(second) && variable.len() == 2;
+        if variable.len() > 1 && !last_pair {
+            let i = variable[1];
+            self.error(
+                format!("{path}.arguments[{i}].{}", range_field(&def.arguments[i])),
+                format!(
+                    "only one positional argument may accept a variable number of values, also \
+                     accepted by {path}.arguments[{}]",
+                    variable[0]
+                ),
+            );
+        }
+    }
+
+    fn command(&mut self, def: &CommandDef, path: &str, inherited: &Scope) {
+        self.name(format!("{path}.name"), &def.name);
+        self.description(format!("{path}.description"), &def.description);
+
+        let mut scope = inherited.clone();
+        let mut globals = inherited.clone();
+        for (i, option) in def.options.iter().enumerate() {
+            let path = format!("{path}.options[{i}]");
+            self.name(format!("{path}.id"), &option.id);
+            self.description(format!("{path}.description"), &option.description);
+
+            let mut shorts = Vec::new();
+            let mut longs = Vec::new();
+            match (&option.short, &option.long) {
+                (None, None) => longs.push((option.id.as_str(), format!("{path}.id"))),
+                (short, long) => {
+                    shorts.extend(short.map(|s| (s, format!("{path}.short"))));
+                    longs.extend(long.iter().map(|l| (l.as_str(), format!("{path}.long"))));
+                }
+            }
+            for (field, aliases) in [
+                ("visible_aliases", &option.visible_aliases),
+                ("hidden_aliases", &option.hidden_aliases),
+            ] {
+                for (j, alias) in aliases.iter().enumerate() {
+                    longs.push((alias.as_str(), format!("{path}.{field}[{j}]")));
+                }
+            }
+            for (field, aliases) in [
+                ("visible_short_aliases", &option.visible_short_aliases),
+                ("hidden_short_aliases", &option.hidden_short_aliases),
+            ] {
+                for (j, alias) in aliases.iter().enumerate() {
+                    shorts.push((*alias, format!("{path}.{field}[{j}]")));
+                }
+            }
+
+            let mut flags = Vec::new();
+            for (short, short_path) in shorts {
+                if short == '-' {
+                    self.error(short_path, "short names cannot be '-'".to_string());
+                    continue;
+                }
+                flags.push((format!("-{short}"), short_path));
+            }
+            for (long, long_path) in longs {
+                if long.is_empty() {
+                    self.error(long_path, "empty name".to_string());
+                    continue;
+                }
+                if long.starts_with('-') {
+                    self.error(
+                        long_path,
+                        "long names must not start with '-', it is added by the parser".to_string(),
+                    );
+                    continue;
+                }
+                flags.push((format!("--{long}"), long_path));
+            }
+
+            scope.shadow(inherited, &option.id);
+            globals.shadow(inherited, &option.id);
+            self.unique(
+                &mut scope.ids,
+                "argument id",
+                option.id.clone(),
+                format!("{path}.id"),
+            );
+            for (flag, flag_path) in flags {
+                if option.global {
+                    let owner = (flag_path.clone(), option.id.clone());
+                    globals.flags.insert(flag.clone(), owner);
+                }
+                match scope.flags.get(&flag) {
+                    Some((first, _)) => self.error(
+                        flag_path,
+                        format!("duplicate option name '{flag}', also used by {first}"),
+                    ),
+                    None => {
+                        scope.flags.insert(flag, (flag_path, option.id.clone()));
+                    }
+                }
+            }
+            if option.global {
+                globals.ids.insert(option.id.clone(), format!("{path}.id"));
+            }
+
+            let takes_value = matches!(option.action, OptionAction::Set | OptionAction::Append);
+            if takes_value && is_command_line(&option.value_hint, &option.value_type) {
+                let field = match option.value_hint {
+                    Some(_) => "value_hint",
+                    None => "value_type",
+                };
+                self.error(
+                    format!("{path}.{field}"),
+                    "only positional arguments may take a command_line value".to_string(),
+                );
+            }
+            if !takes_value && !option.possible_values.is_empty() {
+                self.warning(
+                    format!("{path}.possible_values"),
+                    "possible_values are ignored for flag and count options".to_string(),
+                );
+            }
+            self.values(
+                &path,
+                &option.value_type,
+                &option.value_hint,
+                &option.num_args,
+                &option.possible_values,
+            );
+        }
+
+        let mut optional_positional = None;
+        let mut last_positional = None;
+        for (i, argument) in def.arguments.iter().enumerate() {
+            let path = format!("{path}.arguments[{i}]");
+            self.name(format!("{path}.name"), &argument.name);
+            self.description(format!("{path}.description"), &argument.description);
+            scope.shadow(inherited, &argument.name);
+            globals.shadow(inherited, &argument.name);
+            self.unique(
+                &mut scope.ids,
+                "argument id",
+                argument.name.clone(),
+                format!("{path}.name"),
+            );
+            if argument.global {
+                globals
+                    .ids
+                    .insert(argument.name.clone(), format!("{path}.name"));
+            }
+
+            match &optional_positional {
+                _ if !argument.required => {
+                    optional_positional.get_or_insert(path.clone());
+                }
+                Some(optional) if !argument.last => self.error(
+                    format!("{path}.required"),
+                    format!("required positional argument follows the optional {optional}"),
+                ),
+                _ => {}
+            }
+            let command_line = is_command_line(&argument.value_hint, &argument.value_type);
+            let what = if argument.trailing_var_arg {
+                "a trailing var arg"
+            } else {
+                "a command_line value"
+            };
+            if argument.is_trailing_var_arg() && i + 1 != def.arguments.len() {
+                self.error(
+                    format!("{path}.{}", trailing_field(argument)),
+                    format!("only the last positional argument may be {what}"),
+                );
+            }
+            if argument.trailing_var_arg && argument.last {
+                self.error(
+                    format!("{path}.trailing_var_arg"),
+                    "a trailing var arg cannot also be last".to_string(),
+                );
+            }
+            if argument.last {
+                match &last_positional {
+                    Some(first) => self.error(
+                        format!("{path}.last"),
+                        format!("only one positional argument may be last, also set on {first}"),
+                    ),
+                    None => last_positional = Some(path.clone()),
+                }
+                if argument.required && !def.subcommands.is_empty() {
+                    self.error(
+                        format!("{path}.required"),
+                        "a required last positional argument cannot be combined with subcommands"
+                            .to_string(),
+                    );
+                }
+            }
+            if positional_range(argument).is_some_and(|r| r.max_values() == 0) {
+                self.error(
+                    format!("{path}.num_args"),
+                    "positional arguments must take at least one value".to_string(),
+                );
+            }
+            let single = argument.num_args.is_some() && !multiple(&argument.num_args);
+            if (argument.trailing_var_arg || command_line) && single {
+                self.error(
+                    format!("{path}.num_args"),
+                    format!("{what} must accept multiple values"),
+                );
+            }
+            self.values(
+                &path,
+                &argument.value_type,
+                &argument.value_hint,
+                &argument.num_args,
+                &argument.possible_values,
+            );
+        }
+
+        self.multiple_positionals(def, path);
+
+        let mut groups = HashMap::new();
+        for (i, group) in def.groups.iter().enumerate() {
+            let path = format!("{path}.groups[{i}]");
+            self.name(format!("{path}.name"), &group.name);
+            self.unique(
+                &mut groups,
+                "group name",
+                group.name.clone(),
+                format!("{path}.name"),
+            );
+            if let Some(first) = scope.ids.get(&group.name) {
+                self.error(
+                    format!("{path}.name"),
+                    format!(
+                        "group name '{}' is also an argument id, used by {first}",
+                        group.name
+                    ),
+                );
+            }
+        }
+
+        let args = scope.ids.keys().map(String::as_str).collect::<Vec<_>>();
+        let mut known = args.clone();
+        known.extend(def.groups.iter().map(|g| g.name.as_str()));
+        for (i, option) in def.options.iter().enumerate() {
+            let path = format!("{path}.options[{i}]");
+            self.self_reference(&path, "conflicts_with", &option.id, &option.conflicts_with);
+            self.self_reference(&path, "requires", &option.id, &option.requires);
+            self.required(
+                &path,
+                option.required,
+                option.global,
+                &option.required_unless_present,
+            );
+            self.references(&path, "conflicts_with", &option.conflicts_with, &known);
+            self.references(&path, "requires", &option.requires, &known);
+            self.references(
+                &path,
+                "required_unless_present",
+                &option.required_unless_present,
+                &known,
+            );
+        }
+        for (i, argument) in def.arguments.iter().enumerate() {
+            let path = format!("{path}.arguments[{i}]");
+            self.self_reference(
+                &path,
+                "conflicts_with",
+                &argument.name,
+                &argument.conflicts_with,
+            );
+            self.self_reference(&path, "requires", &argument.name, &argument.requires);
+            self.required(
+                &path,
+                argument.required,
+                argument.global,
+                &argument.required_unless_present,
+            );
+            self.references(&path, "conflicts_with", &argument.conflicts_with, &known);
+            self.references(&path, "requires", &argument.requires, &known);
+            self.references(
+                &path,
+                "required_unless_present",
+                &argument.required_unless_present,
+                &known,
+            );
+        }
+        for (i, group) in def.groups.iter().enumerate() {
+            let path = format!("{path}.groups[{i}]");
+            self.references(&path, "args", &group.args, &args);
+            self.references(&path, "conflicts_with", &group.conflicts_with, &known);
+            self.references(&path, "requires", &group.requires, &known);
+        }
+
+        let mut subcommands = HashMap::new();
+        for (i, sub) in def.subcommands.iter().enumerate() {
+            let path = format!("{path}.subcommands[{i}]");
+            self.unique(
+                &mut subcommands,
+                "subcommand name",
+                sub.name.clone(),
+                format!("{path}.name"),
+            );
+            for (field, aliases) in [
+                ("aliases", &sub.aliases),
+                ("visible_aliases", &sub.visible_aliases),
+            ] {
+                for (j, alias) in aliases.iter().enumerate() {
+                    self.name(format!("{path}.{field}[{j}]"), alias);
+                    self.unique(
+                        &mut subcommands,
+                        "subcommand name",
+                        alias.clone(),
+                        format!("{path}.{field}[{j}]"),
+                    );
+                }
+            }
+            self.command(sub, &path, &globals);
+        }
+    }
+}
+
+/// Checks the whole spec rooted at `def`, returning every problem found.
+///
+/// Names must be unique within a command including the globals inherited from its parents, and
+/// must not be or start with `-`. Relationships must name existing arguments other than the
+/// argument itself, and required arguments must be neither global nor `required_unless_present`.
+/// `value_hint` and `num_args` must parse, positional arguments must not follow optional ones unless
+/// they are `last`, take at least one value, only the last or second to last may take multiple
+/// values, only the last may be a trailing var arg, at most one may be `last`, and groups must not be
+/// named like an argument. These would otherwise panic in clap's debug assertions. Unknown value
+/// types, possible values not matching the value type and empty descriptions are reported as
+/// warnings.
+pub fn check_spec(def: &CommandDef) -> Vec<Problem> {
+    let mut checker = Checker::default();
+    checker.command(def, "$.command", &Scope::default());
+    checker.problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn check(command: Value) -> Vec<(Severity, String, String)> {
+        let def = serde_json::from_value(command).unwrap();
+        check_spec(&def)
+            .into_iter()
+            .map(|p| (p.severity, p.path, p.message))
+            .collect()
+    }
+
+    fn errors(command: Value) -> Vec<(String, String)> {
+        check(command)
+            .into_iter()
+            .filter(|(severity, ..)| *severity == Severity::Error)
+            .map(|(_, path, message)| (path, message))
+            .collect()
+    }
+
+    fn error(path: &str, message: &str) -> (String, String) {
+        (path.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn valid_spec_has_no_problems() {
+        let problems = check(json!({
+            "name": "t",
+            "description": "t",
+            "options": [{ "id": "verbose", "short": "v", "description": "v", "action": "flag" }],
+            "arguments": [{ "name": "file", "description": "f", "required": true }],
+            "subcommands": [{ "name": "run", "description": "r", "aliases": ["r"] }],
+        }));
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn duplicate_flags_through_globals() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [{ "id": "verbose", "short": "v", "description": "v", "global": true }],
+            "subcommands": [{
+                "name": "run",
+                "description": "r",
+                "options": [{ "id": "version", "short": "v", "description": "v" }],
+            }],
+        }));
+        assert_eq!(
+            problems,
+            [error(
+                "$.command.subcommands[0].options[0].short",
+                "duplicate option name '-v', also used by $.command.options[0].short"
+            )]
+        );
+    }
+
+    #[test]
+    fn globals_can_be_shadowed_by_id() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [{ "id": "verbose", "short": "v", "description": "v", "global": true }],
+            "subcommands": [{
+                "name": "run",
+                "description": "r",
+                "options": [{ "id": "verbose", "short": "v", "description": "v" }],
+                "subcommands": [{
+                    "name": "fast",
+                    "description": "f",
+                    "options": [{ "id": "other", "short": "v", "description": "v" }],
+                }],
+            }],
+        }));
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn unknown_references() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "a", "description": "a", "conflicts_with": ["b", "group"] },
+                { "id": "c", "description": "c", "requires": ["missing"] },
+            ],
+            "groups": [{ "name": "group", "args": ["a", "group"] }],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.options[0].conflicts_with[0]",
+                    "unknown argument or group 'b'"
+                ),
+                error(
+                    "$.command.options[1].requires[0]",
+                    "unknown argument or group 'missing'"
+                ),
+                error(
+                    "$.command.groups[0].args[1]",
+                    "unknown argument or group 'group'"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn positional_ordering() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "arguments": [
+                { "name": "a", "description": "a" },
+                { "name": "b", "description": "b", "required": true },
+                { "name": "c", "description": "c", "required": true, "last": true },
+            ],
+        }));
+        assert_eq!(
+            problems,
+            [error(
+                "$.command.arguments[1].required",
+                "required positional argument follows the optional $.command.arguments[0]"
+            )]
+        );
+    }
+
+    #[test]
+    fn trailing_var_args() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "arguments": [
+                { "name": "a", "description": "a", "trailing_var_arg": true },
+                { "name": "b", "description": "b", "trailing_var_arg": true, "num_args": "..=1" },
+            ],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.arguments[0].trailing_var_arg",
+                    "only the last positional argument may be a trailing var arg"
+                ),
+                error(
+                    "$.command.arguments[1].num_args",
+                    "a trailing var arg must accept multiple values"
+                ),
+            ]
+        );
+
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "arguments": [{ "name": "a", "description": "a", "trailing_var_arg": true }],
+        }));
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn command_line_values() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [{ "id": "exec", "description": "e", "value_type": "command_line" }],
+            "subcommands": [
+                {
+                    "name": "a",
+                    "description": "a",
+                    "arguments": [
+                        { "name": "cmd", "description": "c", "value_type": "command_line" },
+                        { "name": "b", "description": "b" },
+                    ],
+                },
+                {
+                    "name": "c",
+                    "description": "c",
+                    "arguments": [{
+                        "name": "cmd",
+                        "description": "c",
+                        "value_hint": "command_with_arguments",
+                        "num_args": 1,
+                    }],
+                },
+                {
+                    "name": "d",
+                    "description": "d",
+                    "arguments": [{ "name": "cmd", "description": "c", "value_type": "command_line" }],
+                },
+            ],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.options[0].value_type",
+                    "only positional arguments may take a command_line value"
+                ),
+                error(
+                    "$.command.subcommands[0].arguments[0].value_type",
+                    "only the last positional argument may be a command_line value"
+                ),
+                error(
+                    "$.command.subcommands[1].arguments[0].num_args",
+                    "a command_line value must accept multiple values"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_starting_with_a_dash() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "a", "description": "a", "long": "-a" },
+                { "id": "b", "description": "b", "short": "-", "visible_aliases": ["-b"] },
+            ],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.options[0].long",
+                    "long names must not start with '-', it is added by the parser"
+                ),
+                error("$.command.options[1].short", "short names cannot be '-'"),
+                error(
+                    "$.command.options[1].visible_aliases[0]",
+                    "long names must not start with '-', it is added by the parser"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn self_references_and_required() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "a", "description": "a", "conflicts_with": ["b", "a"], "requires": ["a"] },
+                { "id": "b", "description": "b", "action": "set", "required": true, "global": true },
+                {
+                    "id": "c",
+                    "description": "c",
+                    "action": "set",
+                    "required": true,
+                    "required_unless_present": ["a"],
+                },
+            ],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.options[0].conflicts_with[1]",
+                    "argument 'a' cannot name itself in conflicts_with"
+                ),
+                error(
+                    "$.command.options[0].requires[0]",
+                    "argument 'a' cannot name itself in requires"
+                ),
+                error(
+                    "$.command.options[1].required",
+                    "global arguments cannot be required"
+                ),
+                error(
+                    "$.command.options[2].required_unless_present",
+                    "required arguments cannot also be required_unless_present"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn positional_values() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "arguments": [
+                { "name": "a", "description": "a", "num_args": 0 },
+                { "name": "b", "description": "b", "last": true },
+                { "name": "c", "description": "c", "last": true, "required": true },
+            ],
+            "subcommands": [{ "name": "s", "description": "s" }],
+        }));
+        assert_eq!(
+            problems,
+            [
+                error(
+                    "$.command.arguments[0].num_args",
+                    "positional arguments must take at least one value"
+                ),
+                error(
+                    "$.command.arguments[2].last",
+                    "only one positional argument may be last, also set on $.command.arguments[1]"
+                ),
+                error(
+                    "$.command.arguments[2].required",
+                    "a required last positional argument cannot be combined with subcommands"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiple_positionals() {
+        let positionals = |arguments: Value| {
+            errors(json!({ "name": "t", "description": "t", "arguments": arguments }))
+        };
+        assert_eq!(
+            positionals(json!([
+                { "name": "a", "description": "a", "num_args": "1..", "required": true },
+                { "name": "b", "description": "b", "required": true },
+                { "name": "c", "description": "c", "required": true },
+            ])),
+            [error(
+                "$.command.arguments[0].num_args",
+                "only the last or second to last positional argument may accept multiple values"
+            )]
+        );
+        assert_eq!(
+            positionals(json!([
+                { "name": "a", "description": "a", "num_args": "1..", "required": true },
+                { "name": "b", "description": "b" },
+            ])),
+            [error(
+                "$.command.arguments[1].required",
+                "positional argument after $.command.arguments[0], which accepts multiple \
+                 values, must be required or last"
+            )]
+        );
+        assert_eq!(
+            positionals(json!([
+                { "name": "a", "description": "a", "num_args": "1..", "required": true },
+                { "name": "b", "description": "b", "trailing_var_arg": true, "required": true },
+            ])),
+            [error(
+                "$.command.arguments[1].trailing_var_arg",
+                "only one positional argument may accept a variable number of values, also \
+                 accepted by $.command.arguments[0]"
+            )]
+        );
+
+        // Accepted layouts must also pass clap's own assertions.
+        for arguments in [
+            json!([
+                { "name": "a", "description": "a", "num_args": "1..", "required": true },
+                { "name": "b", "description": "b", "required": true },
+            ]),
+            json!([
+                { "name": "a", "description": "a", "num_args": 2, "required": true },
+                { "name": "b", "description": "b", "num_args": "1..", "required": true },
+            ]),
+            json!([
+                { "name": "a", "description": "a", "num_args": "1.." },
+                { "name": "b", "description": "b", "num_args": "1..", "last": true },
+            ]),
+        ] {
+            let command = json!({ "name": "t", "description": "t", "arguments": arguments });
+            let def = serde_json::from_value(command).unwrap();
+            assert!(check_spec(&def).is_empty());
+            crate::make_command(&def, &[]).debug_assert();
+        }
+    }
+
+    #[test]
+    fn group_named_like_an_argument() {
+        let problems = errors(json!({
+            "name": "t",
+            "description": "t",
+            "options": [{ "id": "a", "description": "a" }],
+            "groups": [{ "name": "a" }],
+        }));
+        assert_eq!(
+            problems,
+            [error(
+                "$.command.groups[0].name",
+                "group name 'a' is also an argument id, used by $.command.options[0].id"
+            )]
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        let problems = check(json!({
+            "name": "t",
+            "description": "",
+            "options": [{
+                "id": "level",
+                "description": "l",
+                "value_type": "integer",
+                "possible_values": ["1", "high"],
+            }],
+        }));
+        let warnings = problems
+            .iter()
+            .map(|(severity, path, _)| (*severity, path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                (Severity::Warning, "$.command.description"),
+                (Severity::Warning, "$.command.options[0].possible_values[1]"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use clap::{ValueEnum, builder::PossibleValue};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// How files embedded into the generated C++ header or C sources are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Byte by byte hex arrays.
+    Hex,
+    /// Raw string literals, C++ only.
+    RawString,
+    /// `constexpr std::string_view` constants, C++17 only.
+    StringView,
+    /// C23 `#embed` directives referencing sidecar files written next to the output.
+    Embed,
+}
+
+impl ValueEnum for Encoding {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Encoding::Hex,
+            Encoding::RawString,
+            Encoding::StringView,
+            Encoding::Embed,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Encoding::Hex => "hex",
+            Encoding::RawString => "raw-string",
+            Encoding::StringView => "string-view",
+            Encoding::Embed => "embed",
+        }))
+    }
+}
+
+/// Every C++20 keyword and alternative operator token.
+const CPP_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char16_t",
+    "char32_t",
+    "char8_t",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "compl",
+    "concept",
+    "const",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+/// Turns `s` into a snake_case C++ identifier, e.g. `dry-run` into `dry_run`.
+pub fn identifier(s: &str) -> String {
+    let mut ident = s
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if CPP_KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+/// Quotes `s` as a C++ string literal. Octal escapes are used for control characters since hex
+/// escapes would swallow any hex digits following them.
+pub fn cpp_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in s.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\t' => literal.push_str("\\t"),
+            b'?' => literal.push_str("\\?"),
+            0x20..=0x7E => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Writes `buf` as comma separated hex literals, 12 bytes per line.
+pub fn write_bytes(out: &mut impl Write, buf: &[u8]) {
+    for (i, byte) in buf.iter().enumerate() {
+        if i % 12 == 0 {
+            write!(out, "    ").unwrap();
+        }
+        write!(out, "0x{:02X}, ", byte).unwrap();
+        if i % 12 == 11 || i == buf.len() - 1 {
+            writeln!(out).unwrap();
+        }
+    }
+}
+
+/// Files referenced by `#embed` directives, to be written next to the generated header.
+pub type Sidecars = Vec<(PathBuf, Vec<u8>)>;
+
+/// The file next to `header_path` holding the `#embed`ed content for `key`, e.g.
+/// `completions.bash` for `completions.hpp`.
+pub fn sidecar_path(header_path: &Path, key: &str) -> PathBuf {
+    let stem = header_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .expect("output file name is checked by run");
+    header_path.with_file_name(format!("{stem}.{key}"))
+}
+
+/// Writes the `#embed` directive for `path`, which is resolved relative to the including header.
+pub fn write_embed(out: &mut impl Write, path: &Path) {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap();
+    writeln!(out, "#embed \"{}\"", file_name).unwrap();
+}
+
+/// The opening and closing lines of an include guard of kind `guard` (`pragma`, `macro` or
+/// `none`) for a header called `file_name`.
+pub fn include_guard(guard: &str, file_name: &str) -> (String, String) {
+    match guard {
+        "pragma" => ("#pragma once\n\n".to_string(), String::new()),
+        "macro" => {
+            let name = format!("{}_INCLUDED", identifier(file_name).to_ascii_uppercase());
+            (
+                format!("#ifndef {name}\n#define {name}\n\n"),
+                format!("\n#endif /* {name} */\n"),
+            )
+        }
+        _ => (String::new(), String::new()),
+    }
+}
+
+/// Compiles generated code in tests, which are skipped where the compiler is not installed.
+#[cfg(test)]
+pub mod testing {
+    use std::{
+        ops::Deref,
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    /// A scratch directory, removed again when dropped.
+    pub struct Scratch(PathBuf);
+
+    impl Deref for Scratch {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes `files` to a scratch directory named after `test` and runs `compiler` with `args` in
+    /// it, returning the directory, or `None` when `compiler` is not installed.
+    pub fn compile(
+        compiler: &str,
+        test: &str,
+        files: &[(&str, &str)],
+        args: &[&str],
+    ) -> Option<Scratch> {
+        let dir =
+            Scratch(std::env::temp_dir().join(format!("clapper-{test}-{}", std::process::id())));
+        std::fs::create_dir_all(&*dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        let output = match Command::new(compiler)
+            .args(args)
+            .current_dir(&*dir)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("skipping, {compiler} is not installed");
+                return None;
+            }
+            Err(e) => panic!("unable to run {compiler}: {e}"),
+        };
+        assert!(
+            output.status.success(),
+            "{compiler} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(dir)
+    }
+
+    /// Runs `program` from `dir` with the extra environment `env`, returning its stdout.
+    pub fn run(dir: &Path, program: &str, env: &[(&str, &str)], args: &[&str]) -> String {
+        let output = Command::new(dir.join(program))
+            .args(args)
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{program} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    ArgumentDef, CommandDef, OptionAction, OptionDef, PossibleValueDef,
+    codegen::{cpp_string, identifier, include_guard},
+    is_command_line, make_value_hint, parse_value_range,
+};
+use clap::ValueHint;
+use std::{fmt::Write, path::Path};
+
+const RUNTIME: &str = include_str!("cpp_complete/runtime.hpp");
+
+fn strings<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    let items = items.into_iter().map(cpp_string).collect::<Vec<_>>();
+    format!("{{{}}}", items.join(", "))
+}
+
+fn value_kind(value_hint: &Option<String>, value_type: &Option<String>) -> &'static str {
+    match make_value_hint(value_hint, value_type) {
+        ValueHint::DirPath => "ValueKind::Dir",
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::ExecutablePath => "ValueKind::Path",
+        _ => "ValueKind::Text",
+    }
+}
+
+fn values(possible_values: &[PossibleValueDef]) -> String {
+    let values = possible_values
+        .iter()
+        .filter(|v| !v.is_hidden())
+        .map(|v| {
+            format!(
+                "{{{}, {}}}",
+                cpp_string(v.value()),
+                cpp_string(v.description().unwrap_or(""))
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", values.join(", "))
+}
+
+/// An `ArgSpec` initializer for `option`.
+fn option_spec(option: &OptionDef) -> String {
+    let shorts = option
+        .short
+        .iter()
+        .chain(&option.visible_short_aliases)
+        .chain(&option.hidden_short_aliases)
+        .map(|c| format!("'{}'", c.escape_default()))
+        .collect::<Vec<_>>();
+    let longs = option
+        .long()
+        .into_iter()
+        .chain(option.visible_aliases.iter().map(String::as_str))
+        .chain(option.hidden_aliases.iter().map(String::as_str));
+    let takes_value = matches!(option.action, OptionAction::Set | OptionAction::Append);
+    let repeatable = matches!(option.action, OptionAction::Append | OptionAction::Count);
+
+    format!(
+        "{{{}, {{{}}}, {}, {}, {takes_value}, {repeatable}, {}, {}, {}, {}, {}, false}}",
+        cpp_string(&option.id),
+        shorts.join(", "),
+        strings(longs),
+        cpp_string(&option.description),
+        option.hide,
+        option.global,
+        value_kind(&option.value_hint, &option.value_type),
+        values(&option.possible_values),
+        strings(option.complete_command.iter().map(String::as_str)),
+    )
+}
+
+/// An `ArgSpec` initializer for the positional `argument`.
+fn argument_spec(argument: &ArgumentDef) -> String {
+    let variadic = argument.trailing_var_arg
+        || is_command_line(&argument.value_hint, &argument.value_type)
+        || argument
+            .num_args
+            .as_ref()
+            .is_some_and(|n| parse_value_range(n).max_values() > 1);
+
+    format!(
+        "{{{}, {{}}, {{}}, {}, true, false, {}, false, {}, {}, {}, {variadic}}}",
+        cpp_string(&argument.name),
+        cpp_string(&argument.description),
+        argument.hide,
+        value_kind(&argument.value_hint, &argument.value_type),
+        values(&argument.possible_values),
+        strings(argument.complete_command.iter().map(String::as_str)),
+    )
+}
+
+/// Writes the `CommandSpec` initializer for `def` and its subcommands.
+fn write_command_spec(out: &mut String, def: &CommandDef, indent: &str) {
+    let inner = format!("{indent}    ");
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "{inner}{},", cpp_string(&def.name)).unwrap();
+    writeln!(
+        out,
+        "{inner}{},",
+        strings(
+            def.aliases
+                .iter()
+                .chain(&def.visible_aliases)
+                .map(String::as_str)
+        )
+    )
+    .unwrap();
+    writeln!(out, "{inner}{},", cpp_string(&def.description)).unwrap();
+    writeln!(out, "{inner}{},", def.hidden).unwrap();
+
+    for args in [
+        def.options.iter().map(option_spec).collect::<Vec<_>>(),
+        def.arguments.iter().map(argument_spec).collect::<Vec<_>>(),
+    ] {
+        writeln!(out, "{inner}{{").unwrap();
+        for arg in args {
+            writeln!(out, "{inner}    {arg},").unwrap();
+        }
+        writeln!(out, "{inner}}},").unwrap();
+    }
+
+    writeln!(out, "{inner}{{").unwrap();
+    for sub in &def.subcommands {
+        write!(out, "{inner}    ").unwrap();
+        write_command_spec(out, sub, &format!("{inner}    "));
+        writeln!(out, ",").unwrap();
+    }
+    writeln!(out, "{inner}}},").unwrap();
+    write!(out, "{indent}}}").unwrap();
+}
+
+/// The scripts registering the completion of `bin`, with `@PROGRAM@` standing for the quoted path
+/// of the binary the runtime fills in.
+fn registration_scripts(bin: &str) -> [(&'static str, String); 3] {
+    let function = format!("_clapper_complete_{}", identifier(bin));
+    [
+        (
+            "bash",
+            format!(
+                r#"{function}() {{
+    local IFS=$'\n'
+    COMPREPLY=($(COMPLETE=bash _CLAPPER_COMPLETE_INDEX="${{COMP_CWORD}}" @PROGRAM@ -- "${{COMP_WORDS[@]}}" 2>/dev/null))
+    if [[ ${{#COMPREPLY[@]}} == 1 && ${{COMPREPLY[0]}} == */ ]]; then
+        compopt -o nospace
+    fi
+}}
+complete -F {function} {bin}
+"#
+            ),
+        ),
+        (
+            "zsh",
+            format!(
+                r#"#compdef {bin}
+{function}() {{
+    local IFS=$'\n'
+    local -a candidates
+    candidates=($(COMPLETE=zsh _CLAPPER_COMPLETE_INDEX="$((CURRENT - 1))" @PROGRAM@ -- "${{words[@]}}" 2>/dev/null))
+    _describe -t values 'values' candidates
+}}
+if [ "$funcstack[1]" = "{function}" ]; then
+    {function} "$@"
+else
+    compdef {function} {bin}
+fi
+"#
+            ),
+        ),
+        (
+            "fish",
+            format!(
+                r#"function {function}
+    set -l words (commandline -opc) (commandline -ct)
+    env COMPLETE=fish _CLAPPER_COMPLETE_INDEX=(math (count $words) - 1) @PROGRAM@ -- $words
+end
+complete -c {bin} -e
+complete -c {bin} -f -a "({function})"
+"#
+            ),
+        ),
+    ]
+}
+
+/// Generates a C++17 header answering completion requests from the binary itself.
+///
+/// The spec is embedded as tables and `complete_from_env(argc, argv)` both prints the registration
+/// script for `COMPLETE=<shell> mytool` and computes the candidates when the shell calls back with
+/// the words typed so far. Since the binary sees the whole command line, completion follows
+/// subcommands, global options and `--` exactly, and an optional `Completer` can make the values
+/// depend on what was given before.
+pub fn generate_cpp_completer(
+    def: &CommandDef,
+    namespace: Option<&str>,
+    include_guard_kind: &str,
+    header_path: Option<&Path>,
+) -> String {
+    let namespace = namespace.map_or_else(|| identifier(&def.name), str::to_string);
+    let file_name = match header_path.and_then(|p| p.file_name()) {
+        Some(file_name) => file_name.to_string_lossy().into_owned(),
+        None => format!("{}_complete.hpp", def.name),
+    };
+    let (guard_start, guard_end) = include_guard(include_guard_kind, &file_name);
+
+    let mut out = guard_start;
+    for header in [
+        "cstddef",
+        "cstdio",
+        "cstdlib",
+        "filesystem",
+        "functional",
+        "iostream",
+        "map",
+        "string",
+        "system_error",
+        "vector",
+    ] {
+        writeln!(out, "#include <{header}>").unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "namespace {namespace} {{").unwrap();
+    writeln!(out).unwrap();
+    out.push_str(RUNTIME);
+    writeln!(out).unwrap();
+
+    writeln!(out, "namespace completion_detail {{").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "inline const CommandSpec& spec() {{").unwrap();
+    write!(out, "    static const CommandSpec root").unwrap();
+    write_command_spec(&mut out, def, "    ");
+    writeln!(out, ";").unwrap();
+    writeln!(out, "    return root;").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "inline const char* registration_template(const std::string& shell) {{"
+    )
+    .unwrap();
+    for (shell, script) in registration_scripts(&def.name) {
+        writeln!(out, "    if (shell == \"{shell}\") {{").unwrap();
+        writeln!(out, "        return").unwrap();
+        for line in script.split_inclusive('\n') {
+            writeln!(out, "            {}", cpp_string(line)).unwrap();
+        }
+        writeln!(out, "            ;").unwrap();
+        writeln!(out, "    }}").unwrap();
+    }
+    writeln!(out, "    return \"\";").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "}}  // namespace completion_detail").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "}}  // namespace {namespace}").unwrap();
+    out.push_str(&guard_end);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::testing::{compile, run};
+    use serde_json::json;
+
+    const MAIN: &str = r#"#include "complete.hpp"
+
+int main(int argc, char** argv) {
+    t::complete_from_env(argc, argv, [](const t::CompletionContext& context) {
+        std::vector<t::CompletionCandidate> candidates;
+        if (context.arg == "remote") {
+            // Candidates must start with the typed part, so the path goes behind it.
+            std::string value = context.current;
+            for (const auto& name : context.path) {
+                value += "/" + name;
+            }
+            candidates.push_back({value, ""});
+        }
+        return candidates;
+    });
+    std::cout << "not completing\n";
+}
+"#;
+
+    #[test]
+    fn candidates() {
+        let def = serde_json::from_value(json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "verbose", "short": "v", "description": "Be loud", "action": "flag", "global": true },
+                { "id": "color", "description": "Coloring", "possible_values": ["auto", "always", "never"] },
+            ],
+            "subcommands": [
+                {
+                    "name": "remote",
+                    "description": "Manage remotes",
+                    "subcommands": [{
+                        "name": "add",
+                        "description": "Add a remote",
+                        "arguments": [
+                            { "name": "name", "description": "n", "possible_values": ["origin", "upstream"] },
+                            { "name": "remote", "description": "r" },
+                        ],
+                    }],
+                },
+                { "name": "secret", "description": "s", "hidden": true },
+            ],
+        }))
+        .unwrap();
+        let header = generate_cpp_completer(&def, None, "pragma", None);
+        let files = [("complete.hpp", header.as_str()), ("main.cpp", MAIN)];
+        let flags = [
+            "-std=c++17",
+            "-Wall",
+            "-Wextra",
+            "-Werror",
+            "-o",
+            "main",
+            "main.cpp",
+        ];
+        let Some(dir) = compile("g++", "cpp-complete", &files, &flags) else {
+            return;
+        };
+        let complete = |shell: &str, words: &[&str]| {
+            let mut args = vec!["--", "t"];
+            args.extend(words);
+            run(&dir, "main", &[("COMPLETE", shell)], &args)
+        };
+
+        assert_eq!(run(&dir, "main", &[], &[]), "not completing\n");
+        assert!(run(&dir, "main", &[("COMPLETE", "bash")], &[]).contains("COMPLETE=bash"));
+        assert_eq!(complete("fish", &[""]), "remote\tManage remotes\n");
+        assert_eq!(complete("fish", &["-"]), "-v\tBe loud\n--color\tColoring\n");
+        assert_eq!(complete("fish", &["-v", "-"]), "--color\tColoring\n");
+        assert_eq!(
+            complete("fish", &["--color=a"]),
+            "--color=auto\n--color=always\n"
+        );
+        assert_eq!(complete("bash", &["--color", "n"]), "never\n");
+        assert_eq!(complete("zsh", &["remote", ""]), "add:Add a remote\n");
+        assert_eq!(
+            complete("fish", &["remote", "add", "-v", ""]),
+            "origin\nupstream\n"
+        );
+        assert_eq!(
+            complete("fish", &["remote", "add", "-v", "origin", "gh"]),
+            "gh/t/remote/add\n"
+        );
+        assert_eq!(complete("fish", &["--", "-"]), "");
+    }
+}
@@ -0,0 +1,471 @@
+/// A completion candidate, with an optional description shown by zsh and fish.
+struct CompletionCandidate {
+    std::string value;
+    std::string description;
+};
+
+/// What is being completed, passed to custom completers.
+struct CompletionContext {
+    /// The binary and subcommand names on the command line so far, e.g. `{"mytool", "remote"}`.
+    std::vector<std::string> path;
+    /// Id of the option or positional argument whose value is being completed.
+    std::string arg;
+    /// The part of the value typed so far.
+    std::string current;
+    /// The values given so far, by option or argument id. Flags get an empty value per occurrence.
+    std::map<std::string, std::vector<std::string>> values;
+};
+
+/// Computes the candidates for a value, returning none to fall back to the spec.
+using Completer = std::function<std::vector<CompletionCandidate>(const CompletionContext&)>;
+
+namespace completion_detail {
+
+enum class ValueKind { Text, Path, Dir };
+
+struct ValueSpec {
+    std::string name;
+    std::string description;
+};
+
+struct ArgSpec {
+    std::string id;
+    std::vector<char> shorts;
+    std::vector<std::string> longs;
+    std::string description;
+    bool takes_value;
+    bool repeatable;
+    bool hidden;
+    bool global;
+    ValueKind kind;
+    std::vector<ValueSpec> values;
+    std::vector<std::string> complete_command;
+    bool variadic;
+};
+
+struct CommandSpec {
+    std::string name;
+    std::vector<std::string> aliases;
+    std::string description;
+    bool hidden;
+    std::vector<ArgSpec> options;
+    std::vector<ArgSpec> positionals;
+    std::vector<CommandSpec> subcommands;
+};
+
+inline const CommandSpec& spec();
+inline const char* registration_template(const std::string& shell);
+
+inline bool starts_with(const std::string& s, const std::string& prefix) {
+    return s.compare(0, prefix.size(), prefix) == 0;
+}
+
+inline const ArgSpec* find_long(const std::vector<const ArgSpec*>& options, const std::string& name) {
+    for (const ArgSpec* option : options) {
+        for (const auto& long_name : option->longs) {
+            if (long_name == name) {
+                return option;
+            }
+        }
+    }
+    return nullptr;
+}
+
+inline const ArgSpec* find_short(const std::vector<const ArgSpec*>& options, char c) {
+    for (const ArgSpec* option : options) {
+        for (char short_name : option->shorts) {
+            if (short_name == c) {
+                return option;
+            }
+        }
+    }
+    return nullptr;
+}
+
+inline const CommandSpec* find_subcommand(const CommandSpec& cmd, const std::string& name) {
+    for (const auto& sub : cmd.subcommands) {
+        if (sub.name == name) {
+            return &sub;
+        }
+        for (const auto& alias : sub.aliases) {
+            if (alias == name) {
+                return &sub;
+            }
+        }
+    }
+    return nullptr;
+}
+
+/// Lists the entries of the directory part of `current` starting with its file name part, with a
+/// trailing `/` for directories.
+inline std::vector<CompletionCandidate> paths(const std::string& current, bool dirs_only) {
+    std::vector<CompletionCandidate> result;
+    std::size_t slash = current.find_last_of('/');
+    std::string dir = slash == std::string::npos ? "" : current.substr(0, slash + 1);
+    std::string prefix = slash == std::string::npos ? current : current.substr(slash + 1);
+
+    std::error_code error;
+    std::filesystem::directory_iterator it(dir.empty() ? "." : dir, error);
+    for (; !error && it != std::filesystem::directory_iterator(); it.increment(error)) {
+        std::string name = it->path().filename().string();
+        if (!starts_with(name, prefix) || (prefix.empty() && name[0] == '.')) {
+            continue;
+        }
+        bool is_dir = it->is_directory(error);
+        if (is_dir) {
+            result.push_back({dir + name + "/", ""});
+        } else if (!dirs_only) {
+            result.push_back({dir + name, ""});
+        }
+    }
+    return result;
+}
+
+inline std::string quote_posix(const std::string& arg) {
+    std::string quoted = "'";
+    for (char c : arg) {
+        quoted += c == '\'' ? std::string("'\\''") : std::string(1, c);
+    }
+    return quoted + "'";
+}
+
+/// Runs a `complete_command` with `current` appended, reading `value\tdescription` lines.
+inline std::vector<CompletionCandidate> run_command(const std::vector<std::string>& command,
+                                                   const std::string& current) {
+    std::vector<CompletionCandidate> result;
+#if defined(__unix__) || defined(__APPLE__)
+    std::string line;
+    for (const auto& arg : command) {
+        line += quote_posix(arg) + " ";
+    }
+    line += quote_posix(current) + " 2>/dev/null";
+
+    FILE* pipe = popen(line.c_str(), "r");
+    if (!pipe) {
+        return result;
+    }
+    std::string output;
+    char buf[4096];
+    for (std::size_t n; (n = std::fread(buf, 1, sizeof(buf), pipe)) > 0;) {
+        output.append(buf, n);
+    }
+    pclose(pipe);
+
+    std::size_t start = 0;
+    while (start < output.size()) {
+        std::size_t end = output.find('\n', start);
+        if (end == std::string::npos) {
+            end = output.size();
+        }
+        std::string entry = output.substr(start, end - start);
+        start = end + 1;
+        if (entry.empty()) {
+            continue;
+        }
+        std::size_t tab = entry.find('\t');
+        if (tab == std::string::npos) {
+            result.push_back({entry, ""});
+        } else {
+            result.push_back({entry.substr(0, tab), entry.substr(tab + 1)});
+        }
+    }
+#else
+    (void)command;
+    (void)current;
+#endif
+    return result;
+}
+
+inline std::vector<CompletionCandidate> values(const ArgSpec& arg, CompletionContext& context,
+                                               const Completer& completer) {
+    context.arg = arg.id;
+    if (completer) {
+        std::vector<CompletionCandidate> custom = completer(context);
+        if (!custom.empty()) {
+            return custom;
+        }
+    }
+    if (!arg.values.empty()) {
+        std::vector<CompletionCandidate> result;
+        for (const auto& value : arg.values) {
+            result.push_back({value.name, value.description});
+        }
+        return result;
+    }
+    if (!arg.complete_command.empty()) {
+        return run_command(arg.complete_command, context.current);
+    }
+    if (arg.kind != ValueKind::Text) {
+        return paths(context.current, arg.kind == ValueKind::Dir);
+    }
+    return {};
+}
+
+inline void keep_matching(std::vector<CompletionCandidate>& candidates, const std::string& prefix) {
+    std::vector<CompletionCandidate> matching;
+    for (auto& candidate : candidates) {
+        if (starts_with(candidate.value, prefix)) {
+            matching.push_back(std::move(candidate));
+        }
+    }
+    candidates = std::move(matching);
+}
+
+/// Bash splits `--name=value` into `--name`, `=` and `value`, this joins them back and adjusts
+/// `index` accordingly.
+inline void join_equals(std::vector<std::string>& words, std::size_t& index) {
+    std::vector<std::string> joined;
+    std::size_t joined_index = 0;
+    for (std::size_t i = 0; i < words.size(); ++i) {
+        if (words[i] == "=" && !joined.empty() && starts_with(joined.back(), "--")) {
+            joined.back() += "=";
+            if (i + 1 < words.size() && i + 1 <= index && words[i + 1] != "=") {
+                joined.back() += words[++i];
+            }
+        } else {
+            joined.push_back(words[i]);
+        }
+        if (i <= index) {
+            joined_index = joined.size() - 1;
+        }
+    }
+    index = index < words.size() ? joined_index : joined.size() + (index - words.size());
+    words = std::move(joined);
+}
+
+/// Computes the candidates for `words[index]`. For bash, values of `--name=value` are returned on
+/// their own since bash only replaces the part after the `=`.
+inline std::vector<CompletionCandidate> candidates(std::vector<std::string> words, std::size_t index,
+                                                   bool bash, const Completer& completer) {
+    if (bash) {
+        join_equals(words, index);
+    }
+
+    const CommandSpec* cmd = &spec();
+    std::vector<const ArgSpec*> options;
+    for (const auto& option : cmd->options) {
+        options.push_back(&option);
+    }
+    CompletionContext context;
+    context.path.push_back(cmd->name);
+    std::size_t positional = 0;
+    bool only_positionals = false;
+    const ArgSpec* pending = nullptr;
+
+    for (std::size_t i = 1; i < index && i < words.size(); ++i) {
+        const std::string& word = words[i];
+        if (pending) {
+            context.values[pending->id].push_back(word);
+            pending = nullptr;
+            continue;
+        }
+        if (!only_positionals && word == "--") {
+            only_positionals = true;
+            continue;
+        }
+        if (!only_positionals && starts_with(word, "--")) {
+            std::size_t eq = word.find('=');
+            std::string name = word.substr(2, eq == std::string::npos ? eq : eq - 2);
+            if (const ArgSpec* option = find_long(options, name)) {
+                if (eq != std::string::npos) {
+                    context.values[option->id].push_back(word.substr(eq + 1));
+                } else if (option->takes_value) {
+                    pending = option;
+                } else {
+                    context.values[option->id].emplace_back();
+                }
+            }
+            continue;
+        }
+        if (!only_positionals && word.size() > 1 && word[0] == '-') {
+            for (std::size_t j = 1; j < word.size(); ++j) {
+                const ArgSpec* option = find_short(options, word[j]);
+                if (!option) {
+                    break;
+                }
+                if (option->takes_value) {
+                    if (j + 1 < word.size()) {
+                        std::size_t start = word[j + 1] == '=' ? j + 2 : j + 1;
+                        context.values[option->id].push_back(word.substr(start));
+                    } else {
+                        pending = option;
+                    }
+                    break;
+                }
+                context.values[option->id].emplace_back();
+            }
+            continue;
+        }
+        if (!only_positionals) {
+            if (const CommandSpec* sub = find_subcommand(*cmd, word)) {
+                std::vector<const ArgSpec*> inherited;
+                for (const ArgSpec* option : options) {
+                    if (option->global) {
+                        inherited.push_back(option);
+                    }
+                }
+                options = std::move(inherited);
+                for (const auto& option : sub->options) {
+                    options.push_back(&option);
+                }
+                cmd = sub;
+                positional = 0;
+                context.path.push_back(sub->name);
+                continue;
+            }
+        }
+        if (positional < cmd->positionals.size()) {
+            const ArgSpec& arg = cmd->positionals[positional];
+            context.values[arg.id].push_back(word);
+            if (!arg.variadic) {
+                ++positional;
+            }
+        }
+    }
+
+    std::string current = index < words.size() ? words[index] : "";
+    std::vector<CompletionCandidate> result;
+
+    if (pending) {
+        context.current = current;
+        result = values(*pending, context, completer);
+        keep_matching(result, current);
+        return result;
+    }
+
+    std::size_t eq = current.find('=');
+    if (!only_positionals && starts_with(current, "--") && eq != std::string::npos) {
+        const ArgSpec* option = find_long(options, current.substr(2, eq - 2));
+        if (!option || !option->takes_value) {
+            return result;
+        }
+        context.current = current.substr(eq + 1);
+        result = values(*option, context, completer);
+        keep_matching(result, context.current);
+        if (!bash) {
+            for (auto& candidate : result) {
+                candidate.value = current.substr(0, eq + 1) + candidate.value;
+            }
+        }
+        return result;
+    }
+
+    if (!only_positionals && starts_with(current, "-")) {
+        for (const ArgSpec* option : options) {
+            if (option->hidden || (!option->repeatable && context.values.count(option->id))) {
+                continue;
+            }
+            for (const auto& long_name : option->longs) {
+                result.push_back({"--" + long_name, option->description});
+            }
+            if (!starts_with(current, "--")) {
+                for (char short_name : option->shorts) {
+                    result.push_back({std::string("-") + short_name, option->description});
+                }
+            }
+        }
+        keep_matching(result, current);
+        return result;
+    }
+
+    if (!only_positionals) {
+        for (const auto& sub : cmd->subcommands) {
+            if (!sub.hidden) {
+                result.push_back({sub.name, sub.description});
+            }
+        }
+    }
+    if (positional < cmd->positionals.size() && !cmd->positionals[positional].hidden) {
+        context.current = current;
+        for (auto& candidate : values(cmd->positionals[positional], context, completer)) {
+            result.push_back(std::move(candidate));
+        }
+    }
+    keep_matching(result, current);
+    return result;
+}
+
+/// Quotes `arg` for use in the registration script for `shell`.
+inline std::string quote(const std::string& shell, const std::string& arg) {
+    if (shell != "fish") {
+        return quote_posix(arg);
+    }
+    std::string quoted = "'";
+    for (char c : arg) {
+        if (c == '\'' || c == '\\') {
+            quoted += '\\';
+        }
+        quoted += c;
+    }
+    return quoted + "'";
+}
+
+}  // namespace completion_detail
+
+/// Handles shell completion requests, exiting the process when one was made.
+///
+/// `COMPLETE=<shell> mytool` prints the script registering the completion for bash, zsh or fish,
+/// to be sourced from the shell's startup file. That script runs
+/// `COMPLETE=<shell> _CLAPPER_COMPLETE_INDEX=<index> mytool -- <words>` at TAB time, which prints
+/// the candidates for `words[index]` one per line. `completer` can provide candidates depending on
+/// the values given so far; when it returns none the values from the spec are used.
+///
+/// Call this first thing in `main`, before parsing the arguments.
+inline void complete_from_env(int argc, const char* const* argv, const Completer& completer = {}) {
+    const char* shell_env = std::getenv("COMPLETE");
+    if (!shell_env || !*shell_env || std::string(shell_env) == "0") {
+        return;
+    }
+    std::string shell = shell_env;
+    if (shell != "bash" && shell != "zsh" && shell != "fish") {
+        std::cerr << "error: unsupported shell for completions: " << shell
+                  << " (supported: bash, zsh, fish)\n";
+        std::exit(1);
+    }
+
+    if (argc < 2 || std::string(argv[1]) != "--") {
+        std::string program = argc > 0 ? argv[0] : completion_detail::spec().name;
+        if (program.find('/') != std::string::npos) {
+            std::error_code error;
+            std::filesystem::path absolute = std::filesystem::absolute(program, error);
+            if (!error) {
+                program = absolute.lexically_normal().string();
+            }
+        }
+        std::string script = completion_detail::registration_template(shell);
+        std::string placeholder = "@PROGRAM@";
+        for (std::size_t at; (at = script.find(placeholder)) != std::string::npos;) {
+            script.replace(at, placeholder.size(), completion_detail::quote(shell, program));
+        }
+        std::cout << script;
+        std::exit(0);
+    }
+
+    std::vector<std::string> words(argv + 2, argv + argc);
+    std::size_t index = words.empty() ? 0 : words.size() - 1;
+    if (const char* index_env = std::getenv("_CLAPPER_COMPLETE_INDEX")) {
+        index = static_cast<std::size_t>(std::strtoul(index_env, nullptr, 10));
+    }
+
+    for (const auto& candidate :
+         completion_detail::candidates(words, index, shell == "bash", completer)) {
+        std::string value = candidate.value;
+        std::string description = candidate.description;
+        for (auto& c : description) {
+            if (c == '\n' || c == '\t') {
+                c = ' ';
+            }
+        }
+        if (shell == "bash") {
+            std::cout << value << "\n";
+        } else if (shell == "zsh") {
+            for (std::size_t at = 0; (at = value.find(':', at)) != std::string::npos; at += 2) {
+                value.insert(at, "\\");
+            }
+            std::cout << value << (description.empty() ? "" : ":" + description) << "\n";
+        } else {
+            std::cout << value << (description.empty() ? "" : "\t" + description) << "\n";
+        }
+    }
+    std::exit(0);
+}
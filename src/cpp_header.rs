@@ -0,0 +1,269 @@
+use crate::{
+    codegen::{
+        Encoding, Sidecars, identifier, include_guard, sidecar_path, write_bytes, write_embed,
+    },
+    error::Error,
+    generate_script, install, man,
+    shell::CompletionShell,
+};
+use clap::Command;
+use std::{
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Picks the raw string delimiter for `text`, extending `clapper` until `)delimiter"` does not
+/// occur in the text.
+fn raw_string_delimiter(text: &str) -> String {
+    (0..)
+        .map(|i| match i {
+            0 => "clapper".to_string(),
+            i => format!("clapper{i}"),
+        })
+        .find(|delimiter| !text.contains(&format!("){delimiter}\"")))
+        .unwrap()
+}
+
+fn raw_string(buf: &[u8]) -> String {
+    let text = std::str::from_utf8(buf).expect("Raw string literals require UTF-8 content");
+    let delimiter = raw_string_delimiter(text);
+    format!("R\"{delimiter}({text}){delimiter}\"")
+}
+
+/// Writes `entries` as a map called `name` from key to content, using the encoding of `options`:
+///
+/// - `hex`: `std::map<std::string, std::vector<std::uint8_t>>` initialised byte by byte
+/// - `raw-string`: `std::map<std::string, std::string>` initialised from raw string literals
+/// - `string-view`: a `constexpr std::string_view` per entry plus a map of them
+/// - `embed`: a byte array per entry filled by C23 `#embed` from a sidecar file, plus the same map
+///   as `hex`; the sidecar files are returned for the caller to write
+fn write_byte_map(
+    out: &mut impl Write,
+    name: &str,
+    entries: &[(String, Vec<u8>)],
+    options: &CppHeaderOptions,
+    header_path: &Path,
+) -> Sidecars {
+    let linkage = if options.inline { "inline " } else { "" };
+    let mut sidecars = Vec::new();
+    match options.encoding {
+        Encoding::RawString => {
+            writeln!(
+                out,
+                "{linkage}const std::map<std::string, std::string> {} = {{",
+                name
+            )
+            .unwrap();
+            for (key, buf) in entries {
+                writeln!(out, "{{ \"{}\", {} }},", key, raw_string(buf)).unwrap();
+            }
+        }
+        Encoding::StringView => {
+            for (key, buf) in entries {
+                writeln!(
+                    out,
+                    "{linkage}constexpr std::string_view {}_{} = {};",
+                    name,
+                    identifier(key),
+                    raw_string(buf)
+                )
+                .unwrap();
+                writeln!(out).unwrap();
+            }
+            writeln!(
+                out,
+                "{linkage}const std::map<std::string, std::string_view> {} = {{",
+                name
+            )
+            .unwrap();
+            for (key, _) in entries {
+                writeln!(out, "{{ \"{}\", {}_{} }},", key, name, identifier(key)).unwrap();
+            }
+        }
+        Encoding::Embed => {
+            for (key, buf) in entries {
+                let path = sidecar_path(header_path, key);
+                writeln!(
+                    out,
+                    "{} const unsigned char {}_{}[] = {{",
+                    if options.inline { "inline" } else { "static" },
+                    name,
+                    identifier(key)
+                )
+                .unwrap();
+                write_embed(out, &path);
+                writeln!(out, "}};").unwrap();
+                writeln!(out).unwrap();
+                sidecars.push((path, buf.clone()));
+            }
+            writeln!(
+                out,
+                "{linkage}const std::map<std::string, std::vector<std::uint8_t>> {} = {{",
+                name
+            )
+            .unwrap();
+            for (key, _) in entries {
+                let array = format!("{}_{}", name, identifier(key));
+                writeln!(
+                    out,
+                    "{{ \"{}\", {{ std::begin({array}), std::end({array}) }} }},",
+                    key
+                )
+                .unwrap();
+            }
+        }
+        Encoding::Hex => {
+            writeln!(
+                out,
+                "{linkage}const std::map<std::string, std::vector<std::uint8_t>> {} = {{",
+                name
+            )
+            .unwrap();
+            for (key, buf) in entries {
+                writeln!(out, "{{ \"{}\", {{", key).unwrap();
+                write_bytes(out, buf);
+                writeln!(out, "}}}},").unwrap();
+            }
+        }
+    }
+    writeln!(out, "}};").unwrap();
+    sidecars
+}
+
+/// Naming, encoding and linkage of the generated C++ header.
+pub struct CppHeaderOptions<'a> {
+    pub embed_man: bool,
+    pub encoding: Encoding,
+    /// Namespace wrapping all variables, e.g. `mytool` or `mytool::completions`.
+    pub namespace: Option<&'a str>,
+    pub shells_macro: &'a str,
+    pub completions_name: &'a str,
+    pub man_pages_name: &'a str,
+    pub include_guard: &'a str,
+    /// Emit C++17 `inline` variables so all translation units share one copy of the data.
+    pub inline: bool,
+    /// Also emit `install_completions` and its helpers.
+    pub install_helper: bool,
+}
+
+/// Generates the C++ header embedding the completion scripts for `shells`, returning it together
+/// with the sidecar files needed by the `embed` encoding.
+pub fn generate_cpp_header(
+    command: &Command,
+    shells: &[CompletionShell],
+    options: &CppHeaderOptions,
+    header_path: &Path,
+) -> Result<(Vec<u8>, Sidecars), Error> {
+    let file_name = header_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .expect("output file name is checked by run");
+    let (guard_start, guard_end) = include_guard(options.include_guard, file_name);
+
+    let mut cpp_source = BufWriter::new(Vec::new());
+    write!(cpp_source, "{guard_start}").unwrap();
+    writeln!(cpp_source, "#include <string>").unwrap();
+    writeln!(cpp_source, "#include <vector>").unwrap();
+    writeln!(cpp_source, "#include <cstdint>").unwrap();
+    writeln!(cpp_source, "#include <map>").unwrap();
+    match options.encoding {
+        Encoding::StringView => writeln!(cpp_source, "#include <string_view>").unwrap(),
+        Encoding::Embed => writeln!(cpp_source, "#include <iterator>").unwrap(),
+        Encoding::Hex | Encoding::RawString => {}
+    }
+    if options.install_helper {
+        for header in install::INCLUDES {
+            writeln!(cpp_source, "#include <{header}>").unwrap();
+        }
+        writeln!(cpp_source, "#ifdef __linux__").unwrap();
+        writeln!(cpp_source, "#include <unistd.h>").unwrap();
+        writeln!(cpp_source, "#endif").unwrap();
+    }
+
+    writeln!(cpp_source).unwrap();
+
+    writeln!(
+        cpp_source,
+        "#define {} {}",
+        options.shells_macro,
+        shells
+            .iter()
+            .map(|s| format!("\"{}\"", s))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+    .unwrap();
+
+    if let Some(namespace) = options.namespace {
+        writeln!(cpp_source).unwrap();
+        writeln!(cpp_source, "namespace {namespace} {{").unwrap();
+        writeln!(cpp_source).unwrap();
+    }
+
+    let scripts = shells
+        .iter()
+        .map(|shell| Ok((shell.to_string(), generate_script(*shell, command)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut sidecars = write_byte_map(
+        &mut cpp_source,
+        options.completions_name,
+        &scripts,
+        options,
+        header_path,
+    );
+
+    if options.embed_man {
+        writeln!(cpp_source).unwrap();
+        sidecars.extend(write_byte_map(
+            &mut cpp_source,
+            options.man_pages_name,
+            &man::generate_man_pages(command),
+            options,
+            header_path,
+        ));
+    }
+
+    if options.install_helper {
+        writeln!(cpp_source).unwrap();
+        write!(
+            cpp_source,
+            "{}",
+            install::generate_install_helper(command.get_name(), options.completions_name)
+        )
+        .unwrap();
+    }
+
+    if let Some(namespace) = options.namespace {
+        writeln!(cpp_source).unwrap();
+        writeln!(cpp_source, "}}  // namespace {namespace}").unwrap();
+    }
+    write!(cpp_source, "{guard_end}").unwrap();
+
+    cpp_source.flush().unwrap();
+    Ok((cpp_source.into_inner().unwrap(), sidecars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_string_delimiter_avoids_the_content() {
+        assert_eq!(raw_string_delimiter("echo \"$@\""), "clapper");
+        assert_eq!(raw_string_delimiter("a )clapper\" b"), "clapper1");
+        assert_eq!(
+            raw_string_delimiter(")clapper\" )clapper1\" )clapper2\""),
+            "clapper3"
+        );
+        // Only the closing sequence matters.
+        assert_eq!(raw_string_delimiter("R\"clapper( )clapper"), "clapper");
+    }
+
+    #[test]
+    fn raw_string_round_trips_the_closing_sequence() {
+        assert_eq!(
+            raw_string(b"x )clapper\" y"),
+            "R\"clapper1(x )clapper\" y)clapper1\""
+        );
+    }
+}
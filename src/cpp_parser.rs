@@ -0,0 +1,1449 @@
+use crate::{
+    ArgumentDef, CommandDef, GroupDef, OptionAction, OptionDef, PossibleValueDef,
+    codegen::{cpp_string, identifier, include_guard},
+    is_command_line, make_command, parse_value_range,
+};
+use clap::Command;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+const RUNTIME: &str = include_str!("cpp_parser/runtime.hpp");
+
+/// Turns `s` into a PascalCase C++ type or enumerator name, e.g. `dry-run` into `DryRun`.
+fn pascal_case(s: &str) -> String {
+    let mut name = s
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect::<String>();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'V');
+    }
+    name
+}
+
+/// Returns `name`, suffixed with the first free number when another generated name in the same
+/// scope already took it, and marks the result as taken.
+fn unique(name: String, taken: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = format!("{name}_{n}");
+        n += 1;
+    }
+    candidate
+}
+
+/// Quotes a multi-line text as adjacent string literals, one per line.
+fn cpp_text(text: &str, indent: &str) -> String {
+    let lines = text
+        .split_inclusive('\n')
+        .map(cpp_string)
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return "\"\"".to_string();
+    }
+    lines.join(&format!("\n{indent}"))
+}
+
+fn cpp_char(c: char) -> String {
+    match c {
+        '\'' => "'\\''".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        c if c.is_ascii_graphic() || c == ' ' => format!("'{c}'"),
+        c => format!("'\\{:03o}'", c as u32 & 0xFF),
+    }
+}
+
+fn cpp_size(n: usize) -> String {
+    if n == usize::MAX {
+        "SIZE_MAX".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Flag,
+    Count,
+    Single,
+    Multi,
+}
+
+#[derive(Clone)]
+struct EnumType {
+    name: String,
+    variants: Vec<(String, Vec<String>)>,
+}
+
+/// Everything needed to declare and fill one member of a generated struct.
+#[derive(Clone)]
+struct Field<'a> {
+    id: &'a str,
+    member: String,
+    display: String,
+    kind: Kind,
+    value_type: String,
+    enum_type: Option<EnumType>,
+    possible_values: Option<String>,
+    default_value: Option<&'a str>,
+    env: Option<&'a str>,
+    required: bool,
+    description: &'a str,
+    min_values: usize,
+    max_values: usize,
+    value_delimiter: Option<char>,
+    allow_hyphen_values: bool,
+    shorts: Vec<char>,
+    longs: Vec<String>,
+    trailing_var_arg: bool,
+    last: bool,
+    conflicts_with: &'a [String],
+    requires: &'a [String],
+    required_unless_present: &'a [String],
+}
+
+impl Field<'_> {
+    /// The member type, declared inside the struct so enums need no qualification.
+    fn cpp_type(&self) -> String {
+        let value_type = match &self.enum_type {
+            Some(e) => e.name.clone(),
+            None => self.value_type.clone(),
+        };
+        match self.kind {
+            Kind::Flag => "bool".to_string(),
+            Kind::Count => "std::uint8_t".to_string(),
+            Kind::Multi => format!("std::vector<{value_type}>"),
+            Kind::Single if self.required || self.default_value.is_some() => value_type,
+            Kind::Single => format!("std::optional<{value_type}>"),
+        }
+    }
+
+    fn initializer(&self) -> &'static str {
+        match self.kind {
+            Kind::Flag => " = false",
+            Kind::Count => " = 0",
+            Kind::Single if self.required || self.default_value.is_some() => "{}",
+            _ => "",
+        }
+    }
+
+    fn delimiter(&self) -> String {
+        match self.value_delimiter {
+            Some(c) => cpp_char(c),
+            None => "std::nullopt".to_string(),
+        }
+    }
+
+    /// Statement converting `value` and storing it in `target`.
+    fn assign(&self, target: &str, struct_name: &str, value: &str, name: &str) -> String {
+        let value_type = match &self.enum_type {
+            Some(e) => format!("{}::{}", struct_name, e.name),
+            None => self.value_type.clone(),
+        };
+        let possible = match &self.possible_values {
+            Some(values) => cpp_string(values),
+            None => "nullptr".to_string(),
+        };
+        let converted = format!("detail::convert<{value_type}>({value}, {name}, {possible})");
+        match self.kind {
+            Kind::Multi => format!("{target}.{}.push_back({converted});", self.member),
+            _ => format!("{target}.{} = {converted};", self.member),
+        }
+    }
+}
+
+fn value_type(value_type: &Option<String>) -> &'static str {
+    match value_type.as_deref() {
+        Some("file") | Some("dir") | Some("path") | Some("executable") => "std::filesystem::path",
+        Some("boolean") => "bool",
+        Some("integer") => "std::int64_t",
+        Some("float") => "double",
+        _ => "std::string",
+    }
+}
+
+fn enum_type(id: &str, possible_values: &[PossibleValueDef]) -> Option<EnumType> {
+    if possible_values.is_empty() {
+        return None;
+    }
+    let mut taken = HashSet::new();
+    let variants = possible_values
+        .iter()
+        .map(|v| {
+            let mut names = vec![v.value().to_string()];
+            if let PossibleValueDef::Detailed { aliases, .. } = v {
+                names.extend(aliases.iter().cloned());
+            }
+            (unique(pascal_case(v.value()), &mut taken), names)
+        })
+        .collect();
+    Some(EnumType {
+        name: pascal_case(id),
+        variants,
+    })
+}
+
+fn possible_values(possible_values: &[PossibleValueDef]) -> Option<String> {
+    if possible_values.is_empty() {
+        return None;
+    }
+    Some(
+        possible_values
+            .iter()
+            .filter(|v| !v.is_hidden())
+            .map(|v| v.value())
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+fn option_field(option: &OptionDef) -> Field<'_> {
+    let (min_values, max_values) = match &option.num_args {
+        Some(num_args) => {
+            let range = parse_value_range(num_args);
+            (range.min_values(), range.max_values())
+        }
+        None => (1, 1),
+    };
+    let kind = match option.action {
+        OptionAction::Flag => Kind::Flag,
+        OptionAction::Count => Kind::Count,
+        OptionAction::Append => Kind::Multi,
+        OptionAction::Set if max_values > 1 || option.value_delimiter.is_some() => Kind::Multi,
+        OptionAction::Set => Kind::Single,
+    };
+
+    let mut shorts = Vec::new();
+    shorts.extend(option.short);
+    shorts.extend(option.visible_short_aliases.iter().cloned());
+    shorts.extend(option.hidden_short_aliases.iter().cloned());
+    let mut longs = Vec::new();
+    longs.extend(option.long().map(str::to_string));
+    longs.extend(option.visible_aliases.iter().cloned());
+    longs.extend(option.hidden_aliases.iter().cloned());
+
+    let display = match (option.long(), option.short) {
+        (Some(long), _) => format!("--{long}"),
+        (None, Some(short)) => format!("-{short}"),
+        (None, None) => option.id.clone(),
+    };
+
+    Field {
+        id: &option.id,
+        member: identifier(&option.id),
+        display,
+        kind,
+        value_type: value_type(&option.value_type).to_string(),
+        enum_type: enum_type(&option.id, &option.possible_values),
+        possible_values: possible_values(&option.possible_values),
+        default_value: option.default_value.as_deref(),
+        env: option.env.as_deref(),
+        required: option.required,
+        description: &option.description,
+        min_values,
+        max_values,
+        value_delimiter: option.value_delimiter,
+        allow_hyphen_values: option.allow_hyphen_values,
+        shorts,
+        longs,
+        trailing_var_arg: false,
+        last: false,
+        conflicts_with: &option.conflicts_with,
+        requires: &option.requires,
+        required_unless_present: &option.required_unless_present,
+    }
+}
+
+fn argument_field(arg: &ArgumentDef) -> Field<'_> {
+    let (min_values, max_values) = match &arg.num_args {
+        Some(num_args) => {
+            let range = parse_value_range(num_args);
+            (range.min_values(), range.max_values())
+        }
+        None if arg.trailing_var_arg || is_command_line(&arg.value_hint, &arg.value_type) => {
+            (1, usize::MAX)
+        }
+        None => (1, 1),
+    };
+    let kind = if max_values > 1 || arg.value_delimiter.is_some() {
+        Kind::Multi
+    } else {
+        Kind::Single
+    };
+
+    Field {
+        id: &arg.name,
+        member: identifier(&arg.name),
+        display: format!("<{}>", arg.name),
+        kind,
+        value_type: value_type(&arg.value_type).to_string(),
+        enum_type: enum_type(&arg.name, &arg.possible_values),
+        possible_values: possible_values(&arg.possible_values),
+        default_value: arg.default_value.as_deref(),
+        env: arg.env.as_deref(),
+        required: arg.required,
+        description: &arg.description,
+        min_values,
+        max_values,
+        value_delimiter: arg.value_delimiter,
+        allow_hyphen_values: arg.allow_hyphen_values,
+        shorts: Vec::new(),
+        longs: Vec::new(),
+        trailing_var_arg: arg.is_trailing_var_arg(),
+        last: arg.last,
+        conflicts_with: &arg.conflicts_with,
+        requires: &arg.requires,
+        required_unless_present: &arg.required_unless_present,
+    }
+}
+
+/// A global option declared by an ancestor, stored in that ancestor's struct.
+#[derive(Clone)]
+struct Inherited<'a> {
+    ancestor: usize,
+    field: Field<'a>,
+}
+
+struct Emitter<'a> {
+    /// The command tree with help flags enabled, used to render help and usage texts.
+    help_root: Command,
+    root_def: &'a CommandDef,
+    /// The struct and parse function names of every command, keyed by its path.
+    names: HashMap<Vec<&'a str>, (String, String)>,
+    structs: String,
+    enums: String,
+    functions: String,
+}
+
+fn struct_name(path: &[&str]) -> String {
+    format!(
+        "{}Args",
+        path.iter().map(|p| pascal_case(p)).collect::<String>()
+    )
+}
+
+fn function_name(path: &[&str]) -> String {
+    let mut name = String::from("parse_command");
+    for part in path {
+        name.push('_');
+        name.push_str(identifier(part).trim_matches('_'));
+    }
+    name
+}
+
+/// Names the structs and parse functions of `def` and its subcommands, numbering any that would
+/// collide, e.g. for a subcommand `a-b` next to a nested `a b`.
+fn name_commands<'a>(
+    def: &'a CommandDef,
+    path: &mut Vec<&'a str>,
+    taken: &mut HashSet<String>,
+    names: &mut HashMap<Vec<&'a str>, (String, String)>,
+) {
+    let struct_name = unique(struct_name(path), taken);
+    let function_name = unique(function_name(path), taken);
+    names.insert(path.clone(), (struct_name, function_name));
+    for sub in &def.subcommands {
+        path.push(&sub.name);
+        name_commands(sub, path, taken, names);
+        path.pop();
+    }
+}
+
+fn uses_short(options: &[&OptionDef], short: char) -> bool {
+    options.iter().any(|o| {
+        o.short == Some(short)
+            || o.visible_short_aliases.contains(&short)
+            || o.hidden_short_aliases.contains(&short)
+    })
+}
+
+fn uses_long(options: &[&OptionDef], long: &str) -> bool {
+    options.iter().any(|o| {
+        o.long() == Some(long)
+            || o.visible_aliases.iter().any(|a| a == long)
+            || o.hidden_aliases.iter().any(|a| a == long)
+    })
+}
+
+/// Re-enables the `-h, --help` flag wherever the spec does not claim `-h` or `--help` itself.
+fn enable_help(mut cmd: Command, def: &CommandDef, inherited: &[&OptionDef]) -> Command {
+    let mut options = inherited.to_vec();
+    options.extend(def.options.iter());
+    if !uses_short(&options, 'h') && !uses_long(&options, "help") {
+        cmd = cmd.disable_help_flag(false);
+    }
+
+    let globals = options
+        .iter()
+        .filter(|o| o.global)
+        .cloned()
+        .collect::<Vec<_>>();
+    for sub in def.subcommands.iter().filter(|s| !s.hidden) {
+        cmd = cmd.mut_subcommand(&sub.name, |c| enable_help(c, sub, &globals));
+    }
+    cmd
+}
+
+impl<'a> Emitter<'a> {
+    /// The help-enabled command for `path`, falling back to a standalone command for hidden
+    /// subcommands which are not part of the completion command tree.
+    fn help_command(&self, path: &[&str], def: &CommandDef) -> Command {
+        let mut cmd = &self.help_root;
+        for name in path {
+            match cmd.find_subcommand(name) {
+                Some(sub) => cmd = sub,
+                None => {
+                    let bin_name = std::iter::once(self.root_def.name.as_str())
+                        .chain(path.iter().copied())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let mut standalone =
+                        enable_help(make_command(def, &[]), def, &[]).bin_name(bin_name);
+                    standalone.build();
+                    return standalone;
+                }
+            }
+        }
+        cmd.clone()
+    }
+
+    fn emit_struct(&mut self, name: &str, def: &CommandDef, fields: &[Field], path: &[&'a str]) {
+        writeln!(self.structs, "struct {name} {{").unwrap();
+
+        for field in fields {
+            if let Some(e) = &field.enum_type {
+                let variants = e
+                    .variants
+                    .iter()
+                    .map(|(v, _)| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    self.structs,
+                    "    enum class {} {{ {} }};",
+                    e.name, variants
+                )
+                .unwrap();
+            }
+        }
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 || fields.iter().any(|f| f.enum_type.is_some()) {
+                writeln!(self.structs).unwrap();
+            }
+            if !field.description.is_empty() {
+                writeln!(self.structs, "    /// {}", field.description).unwrap();
+            }
+            writeln!(
+                self.structs,
+                "    {} {}{};",
+                field.cpp_type(),
+                field.member,
+                field.initializer()
+            )
+            .unwrap();
+        }
+
+        if !def.subcommands.is_empty() {
+            let variants = def
+                .subcommands
+                .iter()
+                .map(|s| {
+                    let mut sub_path = path.to_vec();
+                    sub_path.push(&s.name);
+                    self.names[&sub_path].0.as_str()
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(self.structs).unwrap();
+            writeln!(
+                self.structs,
+                "    /// The subcommand that was invoked, if any."
+            )
+            .unwrap();
+            writeln!(
+                self.structs,
+                "    std::variant<std::monostate, {variants}> subcommand;"
+            )
+            .unwrap();
+        }
+        if def.allow_external_subcommands {
+            writeln!(self.structs).unwrap();
+            writeln!(
+                self.structs,
+                "    /// An unknown subcommand followed by its arguments."
+            )
+            .unwrap();
+            writeln!(
+                self.structs,
+                "    std::vector<std::string> external_subcommand;"
+            )
+            .unwrap();
+        }
+        writeln!(self.structs, "}};").unwrap();
+        writeln!(self.structs).unwrap();
+
+        for field in fields {
+            let Some(e) = &field.enum_type else { continue };
+            writeln!(
+                self.enums,
+                "inline bool from_string(const std::string& value, {name}::{}& out) {{",
+                e.name
+            )
+            .unwrap();
+            for (variant, names) in &e.variants {
+                let condition = names
+                    .iter()
+                    .map(|n| format!("value == {}", cpp_string(n)))
+                    .collect::<Vec<_>>()
+                    .join(" || ");
+                writeln!(self.enums, "    if ({condition}) {{").unwrap();
+                writeln!(self.enums, "        out = {name}::{}::{};", e.name, variant).unwrap();
+                writeln!(self.enums, "        return true;").unwrap();
+                writeln!(self.enums, "    }}").unwrap();
+            }
+            writeln!(self.enums, "    return false;").unwrap();
+            writeln!(self.enums, "}}").unwrap();
+            writeln!(self.enums).unwrap();
+        }
+    }
+
+    fn generate(
+        &mut self,
+        def: &'a CommandDef,
+        path: &[&'a str],
+        ancestors: &[String],
+        inherited: &[Inherited<'a>],
+    ) {
+        let (name, function) = self.names[path].clone();
+        let mut fields = def.options.iter().map(option_field).collect::<Vec<_>>();
+        let options_len = fields.len();
+        fields.extend(def.arguments.iter().map(argument_field));
+
+        // Members share the struct with the subcommand members, and the enums nested in it must
+        // not hide the struct itself or any other generated struct.
+        let mut members = HashSet::new();
+        if !def.subcommands.is_empty() {
+            members.insert("subcommand".to_string());
+        }
+        if def.allow_external_subcommands {
+            members.insert("external_subcommand".to_string());
+        }
+        let mut types = self
+            .names
+            .values()
+            .map(|(s, _)| s.clone())
+            .collect::<HashSet<_>>();
+        for field in &mut fields {
+            field.member = unique(std::mem::take(&mut field.member), &mut members);
+            if let Some(e) = &mut field.enum_type {
+                e.name = unique(std::mem::take(&mut e.name), &mut types);
+            }
+        }
+
+        // Children first, so every struct and function is declared before it is used.
+        let mut child_ancestors = ancestors.to_vec();
+        child_ancestors.push(name.clone());
+        let mut child_inherited = inherited.to_vec();
+        child_inherited.extend(
+            def.options
+                .iter()
+                .zip(&fields)
+                .filter(|(o, _)| o.global)
+                .map(|(_, field)| Inherited {
+                    ancestor: ancestors.len(),
+                    field: field.clone(),
+                }),
+        );
+        for sub in &def.subcommands {
+            let mut sub_path = path.to_vec();
+            sub_path.push(&sub.name);
+            self.generate(sub, &sub_path, &child_ancestors, &child_inherited);
+        }
+
+        self.emit_struct(&name, def, &fields, path);
+
+        let mut help_cmd = self.help_command(path, def);
+        let short_help = help_cmd.render_help().to_string();
+        let long_help = help_cmd.render_long_help().to_string();
+        let usage = help_cmd.render_usage().to_string();
+        let mut scope_options = inherited
+            .iter()
+            .map(|i| find_option(self.root_def, path, i.field.id).unwrap())
+            .collect::<Vec<_>>();
+        scope_options.extend(def.options.iter());
+        let builtin_help = !uses_short(&scope_options, 'h') && !uses_long(&scope_options, "help");
+
+        // The usage doubles as the error footer, so it only points at `--help` where it exists.
+        let mut usage_text = usage.trim_end().to_string();
+        if builtin_help {
+            usage_text.push_str("\n\nFor more information, try '--help'.");
+        }
+
+        let f = &mut self.functions;
+        // Parameters are left unnamed when unused to keep `-Wunused-parameter` quiet.
+        let has_subcommands = !def.subcommands.is_empty();
+        let uses_args = !fields.is_empty() || has_subcommands || def.allow_external_subcommands;
+        let mut params = vec![
+            format!("{name}&{}", if uses_args { " args" } else { "" }),
+            "Lexer& lexer".to_string(),
+        ];
+        for (i, ancestor) in ancestors.iter().enumerate() {
+            let used = has_subcommands || inherited.iter().any(|g| g.ancestor == i);
+            let (a, p) = if used {
+                (format!(" a{i}"), format!(" p{i}"))
+            } else {
+                (String::new(), String::new())
+            };
+            params.push(format!("{ancestor}&{a}"));
+            params.push(format!("std::set<std::string>&{p}"));
+        }
+        writeln!(f, "inline void {function}({}) {{", params.join(", ")).unwrap();
+
+        // Option table: local options, inherited globals, then the built-in help flag.
+        let option_targets = fields[..options_len]
+            .iter()
+            .map(|field| {
+                (
+                    field,
+                    "args".to_string(),
+                    "present".to_string(),
+                    name.clone(),
+                )
+            })
+            .chain(inherited.iter().map(|i| {
+                (
+                    &i.field,
+                    format!("a{}", i.ancestor),
+                    format!("p{}", i.ancestor),
+                    ancestors[i.ancestor].clone(),
+                )
+            }))
+            .collect::<Vec<_>>();
+        writeln!(f, "    static const std::vector<OptionSpec> specs = {{").unwrap();
+        for (field, ..) in &option_targets {
+            let shorts = field
+                .shorts
+                .iter()
+                .map(|c| cpp_char(*c))
+                .collect::<Vec<_>>();
+            let longs = field
+                .longs
+                .iter()
+                .map(|l| cpp_string(l))
+                .collect::<Vec<_>>();
+            let takes_value = matches!(field.kind, Kind::Single | Kind::Multi);
+            writeln!(
+                f,
+                "        {{{{{}}}, {{{}}}, {}}},",
+                shorts.join(", "),
+                longs.join(", "),
+                takes_value
+            )
+            .unwrap();
+        }
+        if builtin_help {
+            writeln!(f, "        {{{{'h'}}, {{\"help\"}}, false}},").unwrap();
+        }
+        writeln!(f, "    }};").unwrap();
+        if builtin_help {
+            writeln!(
+                f,
+                "    static const char* const help =\n        {};",
+                cpp_text(&short_help, "        ")
+            )
+            .unwrap();
+            if long_help != short_help {
+                writeln!(
+                    f,
+                    "    static const char* const long_help =\n        {};",
+                    cpp_text(&long_help, "        ")
+                )
+                .unwrap();
+            }
+        }
+        writeln!(
+            f,
+            "    static const char* const usage = {};",
+            cpp_string(&usage_text)
+        )
+        .unwrap();
+        writeln!(f).unwrap();
+        writeln!(f, "    lexer.usage = usage;").unwrap();
+        writeln!(f, "    std::set<std::string> present;").unwrap();
+
+        let positionals = &fields[options_len..];
+        if !positionals.is_empty() {
+            writeln!(f, "    std::size_t positional = 0;").unwrap();
+            if positionals
+                .iter()
+                .any(|p| p.kind == Kind::Multi && p.max_values != usize::MAX)
+            {
+                writeln!(f, "    std::size_t positional_values = 0;").unwrap();
+            }
+        }
+        writeln!(f, "    while (true) {{").unwrap();
+        let hyphen = positionals
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.allow_hyphen_values)
+            .map(|(i, _)| format!("positional == {i}"))
+            .collect::<Vec<_>>();
+        if hyphen.is_empty() {
+            writeln!(f, "        lexer.hyphen_positional = false;").unwrap();
+        } else {
+            writeln!(
+                f,
+                "        lexer.hyphen_positional = {};",
+                hyphen.join(" || ")
+            )
+            .unwrap();
+        }
+        writeln!(f, "        Token token = lexer.next(specs);").unwrap();
+        writeln!(f, "        if (token.kind == Token::Kind::End) {{").unwrap();
+        writeln!(f, "            break;").unwrap();
+        writeln!(f, "        }}").unwrap();
+        writeln!(f, "        if (token.kind == Token::Kind::Option) {{").unwrap();
+        writeln!(f, "            switch (token.option) {{").unwrap();
+        for (i, (field, target, present, struct_name)) in option_targets.iter().enumerate() {
+            writeln!(f, "            case {i}:").unwrap();
+            match field.kind {
+                Kind::Flag => {
+                    writeln!(f, "                {target}.{} = true;", field.member).unwrap()
+                }
+                Kind::Count => {
+                    writeln!(f, "                increment({target}.{});", field.member).unwrap()
+                }
+                Kind::Single | Kind::Multi => {
+                    writeln!(
+                        f,
+                        "                for (const auto& value : lexer.values(token, {}, {}, {})) {{",
+                        field.min_values,
+                        cpp_size(field.max_values),
+                        field.allow_hyphen_values
+                    )
+                    .unwrap();
+                    writeln!(
+                        f,
+                        "                    for (const auto& part : split(value, {})) {{",
+                        field.delimiter()
+                    )
+                    .unwrap();
+                    writeln!(
+                        f,
+                        "                        {}",
+                        field.assign(target, struct_name, "part", "token.name")
+                    )
+                    .unwrap();
+                    writeln!(f, "                    }}").unwrap();
+                    writeln!(f, "                }}").unwrap();
+                }
+            }
+            writeln!(
+                f,
+                "                {present}.insert({});",
+                cpp_string(field.id)
+            )
+            .unwrap();
+            writeln!(f, "                break;").unwrap();
+        }
+        if builtin_help {
+            writeln!(f, "            default:").unwrap();
+            if long_help != short_help {
+                writeln!(
+                    f,
+                    "                throw HelpRequested(token.name == \"-h\" ? help : long_help);"
+                )
+                .unwrap();
+            } else {
+                writeln!(f, "                throw HelpRequested(help);").unwrap();
+            }
+        }
+        writeln!(f, "            }}").unwrap();
+        writeln!(f, "            continue;").unwrap();
+        writeln!(f, "        }}").unwrap();
+
+        if !def.subcommands.is_empty() {
+            writeln!(f, "        if (!lexer.only_positionals) {{").unwrap();
+            for sub in &def.subcommands {
+                let mut sub_path = path.to_vec();
+                sub_path.push(&sub.name);
+                let condition = std::iter::once(&sub.name)
+                    .chain(sub.aliases.iter())
+                    .chain(sub.visible_aliases.iter())
+                    .map(|n| format!("token.name == {}", cpp_string(n)))
+                    .collect::<Vec<_>>()
+                    .join(" || ");
+                writeln!(f, "            if ({condition}) {{").unwrap();
+                if def.args_conflicts_with_subcommands {
+                    writeln!(f, "                if (!present.empty()) {{").unwrap();
+                    writeln!(
+                        f,
+                        "                    throw ParseError({});",
+                        cpp_string(&format!(
+                            "the subcommand '{}' cannot be used with other arguments",
+                            sub.name
+                        ))
+                    )
+                    .unwrap();
+                    writeln!(f, "                }}").unwrap();
+                }
+                let mut args = vec!["sub".to_string(), "lexer".to_string()];
+                for i in 0..ancestors.len() {
+                    args.push(format!("a{i}"));
+                    args.push(format!("p{i}"));
+                }
+                args.push("args".to_string());
+                args.push("present".to_string());
+                let (sub_struct, sub_function) = &self.names[&sub_path];
+                writeln!(
+                    f,
+                    "                auto& sub = args.subcommand.emplace<{sub_struct}>();"
+                )
+                .unwrap();
+                writeln!(f, "                {sub_function}({});", args.join(", ")).unwrap();
+                writeln!(f, "                lexer.usage = usage;").unwrap();
+                writeln!(f, "                break;").unwrap();
+                writeln!(f, "            }}").unwrap();
+            }
+            writeln!(f, "        }}").unwrap();
+        }
+
+        if def.allow_external_subcommands {
+            let condition = if positionals.is_empty() {
+                "!lexer.only_positionals".to_string()
+            } else {
+                format!("positional >= {}", positionals.len())
+            };
+            writeln!(f, "        if ({condition}) {{").unwrap();
+            writeln!(
+                f,
+                "            args.external_subcommand.push_back(token.name);"
+            )
+            .unwrap();
+            writeln!(f, "            for (auto& rest : lexer.rest()) {{").unwrap();
+            writeln!(
+                f,
+                "                args.external_subcommand.push_back(std::move(rest));"
+            )
+            .unwrap();
+            writeln!(f, "            }}").unwrap();
+            writeln!(f, "            break;").unwrap();
+            writeln!(f, "        }}").unwrap();
+        }
+
+        if let Some(last) = positionals.iter().position(|p| p.last).filter(|l| *l > 0) {
+            writeln!(
+                f,
+                "        if (lexer.only_positionals && positional < {last}) {{"
+            )
+            .unwrap();
+            writeln!(f, "            positional = {last};").unwrap();
+            writeln!(f, "        }}").unwrap();
+        }
+        if positionals.is_empty() {
+            writeln!(
+                f,
+                "        throw ParseError(\"unexpected argument '\" + token.name + \"' found\");"
+            )
+            .unwrap();
+        } else {
+            writeln!(f, "        switch (positional) {{").unwrap();
+            for (i, field) in positionals.iter().enumerate() {
+                writeln!(f, "        case {i}:").unwrap();
+                if field.last {
+                    writeln!(f, "            if (!lexer.only_positionals) {{").unwrap();
+                    writeln!(
+                        f,
+                        "                throw ParseError(\"unexpected argument '\" + token.name + \"' found\");"
+                    )
+                    .unwrap();
+                    writeln!(f, "            }}").unwrap();
+                }
+                writeln!(
+                    f,
+                    "            for (const auto& part : split(token.name, {})) {{",
+                    field.delimiter()
+                )
+                .unwrap();
+                writeln!(
+                    f,
+                    "                {}",
+                    field.assign("args", &name, "part", &cpp_string(&field.display))
+                )
+                .unwrap();
+                writeln!(f, "            }}").unwrap();
+                writeln!(f, "            present.insert({});", cpp_string(field.id)).unwrap();
+                if field.trailing_var_arg {
+                    writeln!(f, "            lexer.only_positionals = true;").unwrap();
+                }
+                match field.kind {
+                    Kind::Multi if field.max_values == usize::MAX => {}
+                    Kind::Multi => {
+                        writeln!(
+                            f,
+                            "            if (++positional_values == {}) {{",
+                            field.max_values
+                        )
+                        .unwrap();
+                        writeln!(f, "                ++positional;").unwrap();
+                        writeln!(f, "                positional_values = 0;").unwrap();
+                        writeln!(f, "            }}").unwrap();
+                    }
+                    _ => writeln!(f, "            ++positional;").unwrap(),
+                }
+                writeln!(f, "            continue;").unwrap();
+            }
+            writeln!(f, "        default:").unwrap();
+            writeln!(
+                f,
+                "            throw ParseError(\"unexpected argument '\" + token.name + \"' found\");"
+            )
+            .unwrap();
+            writeln!(f, "        }}").unwrap();
+        }
+        writeln!(f, "    }}").unwrap();
+
+        // Environment variables and defaults for everything that was not given explicitly.
+        for field in &fields {
+            if field.env.is_none() && field.default_value.is_none() {
+                continue;
+            }
+            let id = cpp_string(field.id);
+            match field.kind {
+                Kind::Flag => {
+                    let Some(env) = field.env else { continue };
+                    writeln!(f, "    if (!present.count({id})) {{").unwrap();
+                    writeln!(f, "        if (auto value = env({})) {{", cpp_string(env)).unwrap();
+                    writeln!(
+                        f,
+                        "            args.{} = convert<bool>(*value, {});",
+                        field.member,
+                        cpp_string(env)
+                    )
+                    .unwrap();
+                    writeln!(f, "            if (args.{}) {{", field.member).unwrap();
+                    writeln!(f, "                present.insert({id});").unwrap();
+                    writeln!(f, "            }}").unwrap();
+                    writeln!(f, "        }}").unwrap();
+                    writeln!(f, "    }}").unwrap();
+                }
+                Kind::Count => {}
+                Kind::Single | Kind::Multi => {
+                    writeln!(f, "    if (!present.count({id})) {{").unwrap();
+                    let mut indent = "    ";
+                    if let Some(env) = field.env {
+                        writeln!(f, "        if (auto value = env({})) {{", cpp_string(env))
+                            .unwrap();
+                        writeln!(
+                            f,
+                            "            for (const auto& part : split(*value, {})) {{",
+                            field.delimiter()
+                        )
+                        .unwrap();
+                        writeln!(
+                            f,
+                            "                {}",
+                            field.assign("args", &name, "part", &cpp_string(env))
+                        )
+                        .unwrap();
+                        writeln!(f, "            }}").unwrap();
+                        writeln!(f, "            present.insert({id});").unwrap();
+                        write!(f, "        }}").unwrap();
+                        if field.default_value.is_some() {
+                            writeln!(f, " else {{").unwrap();
+                            indent = "        ";
+                        } else {
+                            writeln!(f).unwrap();
+                        }
+                    }
+                    if let Some(default) = field.default_value {
+                        writeln!(
+                            f,
+                            "    {indent}for (const auto& part : split({}, {})) {{",
+                            cpp_string(default),
+                            field.delimiter()
+                        )
+                        .unwrap();
+                        writeln!(
+                            f,
+                            "    {indent}    {}",
+                            field.assign("args", &name, "part", &cpp_string(&field.display))
+                        )
+                        .unwrap();
+                        writeln!(f, "    {indent}}}").unwrap();
+                        if field.env.is_some() {
+                            writeln!(f, "        }}").unwrap();
+                        }
+                    }
+                    writeln!(f, "    }}").unwrap();
+                }
+            }
+        }
+
+        // Relationships, resolved against local fields, groups and inherited globals.
+        let scope = Scope {
+            fields: &fields,
+            groups: &def.groups,
+            inherited,
+        };
+        let mut checks = Vec::new();
+        for field in &fields {
+            let unless = field.required_unless_present;
+            if (field.required && field.default_value.is_none()) || !unless.is_empty() {
+                let mut condition = format!("!{}", scope.presence(field.id));
+                for other in unless {
+                    condition.push_str(&format!(" && !{}", scope.presence(other)));
+                }
+                checks.push((condition, missing_message(&field.display)));
+            }
+
+            let mut conflicts = field.conflicts_with.to_vec();
+            for group in def.groups.iter().filter(|g| !g.multiple) {
+                if group.args.iter().any(|a| a == field.id) {
+                    conflicts.extend(group.args.iter().filter(|a| *a != field.id).cloned());
+                }
+            }
+            for other in &conflicts {
+                checks.push((
+                    format!("{} && {}", scope.presence(field.id), scope.presence(other)),
+                    conflict_message(&field.display, &scope.display(other)),
+                ));
+            }
+            for other in field.requires {
+                checks.push((
+                    format!("{} && !{}", scope.presence(field.id), scope.presence(other)),
+                    missing_message(&scope.display(other)),
+                ));
+            }
+        }
+        for group in &def.groups {
+            if group.required {
+                checks.push((
+                    format!("!{}", scope.presence(&group.name)),
+                    missing_message(&scope.display(&group.name)),
+                ));
+            }
+            for other in &group.conflicts_with {
+                checks.push((
+                    format!(
+                        "{} && {}",
+                        scope.presence(&group.name),
+                        scope.presence(other)
+                    ),
+                    conflict_message(&scope.display(&group.name), &scope.display(other)),
+                ));
+            }
+            for other in &group.requires {
+                checks.push((
+                    format!(
+                        "{} && !{}",
+                        scope.presence(&group.name),
+                        scope.presence(other)
+                    ),
+                    missing_message(&scope.display(other)),
+                ));
+            }
+        }
+        if def.subcommand_required
+            && (!def.subcommands.is_empty() || def.allow_external_subcommands)
+        {
+            let mut conditions = Vec::new();
+            if !def.subcommands.is_empty() {
+                conditions.push("args.subcommand.index() == 0");
+            }
+            if def.allow_external_subcommands {
+                conditions.push("args.external_subcommand.empty()");
+            }
+            let full_name = std::iter::once(self.root_def.name.as_str())
+                .chain(path.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let names = def
+                .subcommands
+                .iter()
+                .filter(|s| !s.hidden)
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut message =
+                format!("'{full_name}' requires a subcommand but one was not provided");
+            if !names.is_empty() {
+                message.push_str(&format!("\n  [subcommands: {names}]"));
+            }
+            checks.push((conditions.join(" && "), cpp_string(&message)));
+        }
+        for (condition, message) in checks {
+            writeln!(f, "    if ({condition}) {{").unwrap();
+            writeln!(f, "        throw ParseError({message});").unwrap();
+            writeln!(f, "    }}").unwrap();
+        }
+
+        writeln!(f, "}}").unwrap();
+        writeln!(f).unwrap();
+    }
+}
+
+fn missing_message(display: &str) -> String {
+    cpp_string(&format!(
+        "the following required arguments were not provided:\n  {display}"
+    ))
+}
+
+fn conflict_message(display: &str, other: &str) -> String {
+    cpp_string(&format!(
+        "the argument '{display}' cannot be used with '{other}'"
+    ))
+}
+
+/// Looks up a global option visible at `path` by id, searching from the innermost ancestor.
+fn find_option<'a>(root: &'a CommandDef, path: &[&str], id: &str) -> Option<&'a OptionDef> {
+    let mut defs = vec![root];
+    for name in path {
+        let parent = defs.last().unwrap();
+        defs.push(parent.subcommands.iter().find(|s| s.name == *name)?);
+    }
+    defs.iter()
+        .rev()
+        .find_map(|d| d.options.iter().find(|o| o.id == id))
+}
+
+struct Scope<'a> {
+    fields: &'a [Field<'a>],
+    groups: &'a [GroupDef],
+    inherited: &'a [Inherited<'a>],
+}
+
+impl Scope<'_> {
+    /// C++ expression telling whether the argument or group `id` was given.
+    fn presence(&self, id: &str) -> String {
+        if let Some(group) = self.groups.iter().find(|g| g.name == id) {
+            let members = group
+                .args
+                .iter()
+                .map(|a| self.presence(a))
+                .collect::<Vec<_>>();
+            if members.is_empty() {
+                return "false".to_string();
+            }
+            return format!("({})", members.join(" || "));
+        }
+        if self.fields.iter().any(|f| f.id == id) {
+            return format!("present.count({})", cpp_string(id));
+        }
+        match self.inherited.iter().find(|i| i.field.id == id) {
+            Some(i) => format!("p{}.count({})", i.ancestor, cpp_string(id)),
+            None => "false".to_string(),
+        }
+    }
+
+    fn display(&self, id: &str) -> String {
+        if let Some(group) = self.groups.iter().find(|g| g.name == id) {
+            let members = group
+                .args
+                .iter()
+                .map(|a| self.display(a))
+                .collect::<Vec<_>>();
+            return format!("<{}>", members.join("|"));
+        }
+        self.fields
+            .iter()
+            .chain(self.inherited.iter().map(|i| &i.field))
+            .find(|f| f.id == id)
+            .map(|f| f.display.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// Generates a self-contained C++17 header with a typed struct per (sub)command and a `parse`
+/// function that fills them from `argv`, including `--help` output and clap-style errors.
+///
+/// Everything is declared in `namespace`, defaulting to the binary name, and the include guard is
+/// named after the file name of `header_path` when one is needed.
+pub fn generate_cpp_parser(
+    def: &CommandDef,
+    command: &Command,
+    namespace: Option<&str>,
+    include_guard_kind: &str,
+    header_path: Option<&Path>,
+) -> String {
+    let mut help_root = enable_help(command.clone(), def, &[]);
+    help_root.build();
+
+    let mut names = HashMap::new();
+    name_commands(def, &mut Vec::new(), &mut HashSet::new(), &mut names);
+    let mut generator = Emitter {
+        help_root,
+        root_def: def,
+        names,
+        structs: String::new(),
+        enums: String::new(),
+        functions: String::new(),
+    };
+    generator.generate(def, &[], &[], &[]);
+
+    let namespace = namespace.map_or_else(|| identifier(&def.name), str::to_string);
+    let file_name = match header_path.and_then(|p| p.file_name()) {
+        Some(file_name) => file_name.to_string_lossy().into_owned(),
+        None => format!("{}.hpp", def.name),
+    };
+    let (guard_start, guard_end) = include_guard(include_guard_kind, &file_name);
+
+    let mut out = guard_start;
+    for header in [
+        "cerrno",
+        "cstddef",
+        "cstdint",
+        "cstdlib",
+        "filesystem",
+        "iostream",
+        "optional",
+        "set",
+        "stdexcept",
+        "string",
+        "utility",
+        "variant",
+        "vector",
+    ] {
+        writeln!(out, "#include <{header}>").unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "namespace {namespace} {{").unwrap();
+    writeln!(out).unwrap();
+    out.push_str(RUNTIME);
+    writeln!(out).unwrap();
+    out.push_str(&generator.structs);
+    out.push_str(&generator.enums);
+    writeln!(out, "namespace detail {{").unwrap();
+    writeln!(out).unwrap();
+    out.push_str(&generator.functions);
+    writeln!(out, "}}  // namespace detail").unwrap();
+    writeln!(out).unwrap();
+    out.push_str(
+        "/// Parses `args` (without the program name), throwing `HelpRequested` for `--help` and
+/// `ParseError` for invalid input.
+inline Args try_parse(std::vector<std::string> args) {
+    detail::Lexer lexer(std::move(args));
+    Args result;
+    try {
+        detail::parse_command(result, lexer);
+    } catch (const ParseError& error) {
+        if (error.usage().empty()) {
+            throw ParseError(error.what(), lexer.usage);
+        }
+        throw;
+    }
+    return result;
+}
+
+/// Parses the command line, printing help and exiting with 0 for `--help`, or printing the error
+/// and exiting with 2 for invalid input.
+inline Args parse(int argc, const char* const* argv) {
+    try {
+        return try_parse(std::vector<std::string>(argv + (argc > 0 ? 1 : 0), argv + argc));
+    } catch (const HelpRequested& help) {
+        std::cout << help.what();
+        std::exit(0);
+    } catch (const ParseError& error) {
+        std::cerr << \"error: \" << error.what() << \"\\n\\n\" << error.usage() << \"\\n\";
+        std::exit(2);
+    }
+}
+
+",
+    );
+    writeln!(out, "}}  // namespace {namespace}").unwrap();
+    out.push_str(&guard_end);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::testing::{compile, run};
+    use serde_json::{Value, json};
+
+    fn generate(command: Value, namespace: &str) -> String {
+        let def = serde_json::from_value(command).unwrap();
+        let command = make_command(&def, &[]);
+        generate_cpp_parser(&def, &command, Some(namespace), "pragma", None)
+    }
+
+    /// A spec whose ids and names all map to the same C++ names.
+    fn colliding() -> Value {
+        json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "lang", "description": "l", "possible_values": ["c", "c++", "+", "-"] },
+                { "id": "Foo", "description": "f" },
+                { "id": "foo", "description": "f" },
+                { "id": "subcommand", "description": "s" },
+                { "id": "args", "description": "a", "possible_values": ["x"] },
+                { "id": "static_cast", "description": "s" },
+            ],
+            "subcommands": [
+                { "name": "a-b", "description": "a" },
+                { "name": "a", "description": "a", "subcommands": [{ "name": "b", "description": "b" }] },
+            ],
+        })
+    }
+
+    /// A spec exercising every kind of relationship the parser checks.
+    fn relationships() -> Value {
+        json!({
+            "name": "t",
+            "description": "t",
+            "options": [
+                { "id": "json", "description": "j", "action": "flag", "conflicts_with": ["yaml"] },
+                { "id": "yaml", "description": "y", "action": "flag" },
+                { "id": "user", "description": "u", "requires": ["password"] },
+                { "id": "password", "description": "p" },
+                { "id": "fast", "description": "f", "action": "flag" },
+                { "id": "slow", "description": "s", "action": "flag" },
+            ],
+            "arguments": [{ "name": "file", "description": "f", "required": true }],
+            "groups": [{ "name": "speed", "args": ["fast", "slow"], "required": true }],
+        })
+    }
+
+    #[test]
+    fn generated_names_are_unique() {
+        let header = generate(colliding(), "t");
+        for declaration in [
+            "enum class Lang { C, C_2, V, V_2 };",
+            "enum class Args_2 { X };",
+            "std::optional<std::string> foo;",
+            "std::optional<std::string> foo_2;",
+            "std::optional<std::string> subcommand_2;",
+            "std::optional<std::string> static_cast_;",
+            "std::variant<std::monostate, ABArgs, AArgs> subcommand;",
+            "std::variant<std::monostate, ABArgs_2> subcommand;",
+            "inline void parse_command_a_b_2(",
+        ] {
+            assert!(header.contains(declaration), "missing {declaration}");
+        }
+    }
+
+    #[test]
+    fn help_hides_env_values() {
+        // `PATH` is set wherever the tests run, and must not end up in the generated help.
+        let header = generate(
+            json!({
+                "name": "t",
+                "description": "t",
+                "options": [{ "id": "path", "description": "p", "env": "PATH" }],
+            }),
+            "t",
+        );
+        assert!(header.contains("[env: PATH]"));
+        assert!(!header.contains(&std::env::var("PATH").unwrap()));
+    }
+
+    #[test]
+    fn relationship_checks() {
+        let header = generate(relationships(), "t");
+        for check in [
+            "if (present.count(\"json\") && present.count(\"yaml\")) {\n        \
+             throw ParseError(\"the argument '--json' cannot be used with '--yaml'\");",
+            "if (present.count(\"user\") && !present.count(\"password\")) {\n        \
+             throw ParseError(\"the following required arguments were not provided:\\n  \
+             --password\");",
+            "if (present.count(\"fast\") && present.count(\"slow\")) {\n        \
+             throw ParseError(\"the argument '--fast' cannot be used with '--slow'\");",
+            "if (!(present.count(\"fast\") || present.count(\"slow\"))) {\n        \
+             throw ParseError(\"the following required arguments were not provided:\\n  \
+             <--fast|--slow>\");",
+            "if (!present.count(\"file\")) {\n        \
+             throw ParseError(\"the following required arguments were not provided:\\n  \
+             <file>\");",
+        ] {
+            assert!(header.contains(check), "missing {check}");
+        }
+    }
+
+    #[test]
+    fn generated_parser_compiles_and_checks_relationships() {
+        let cases: &[&[&str]] = &[
+            &["--fast", "f"],
+            &["--json", "--yaml", "--fast", "f"],
+            &["--user", "u", "--fast", "f"],
+            &["--fast", "--slow", "f"],
+            &["f"],
+            &["--slow"],
+        ];
+        let cases = cases
+            .iter()
+            .map(|args| {
+                let args = args.iter().map(|a| cpp_string(a)).collect::<Vec<_>>();
+                format!("{{{}}}", args.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let main = format!(
+            r#"#include "names.hpp"
+#include "relationships.hpp"
+
+int main() {{
+    names::Args names;
+    names.lang = names::Args::Lang::C_2;
+    const std::vector<std::vector<std::string>> cases = {{{cases}}};
+    for (const auto& args : cases) {{
+        try {{
+            relationships::try_parse(args);
+            std::cout << "ok\n";
+        }} catch (const relationships::ParseError& error) {{
+            std::cout << error.what() << "\n";
+        }}
+    }}
+}}
+"#
+        );
+        let files = [
+            ("names.hpp", generate(colliding(), "names")),
+            (
+                "relationships.hpp",
+                generate(relationships(), "relationships"),
+            ),
+            ("main.cpp", main),
+        ];
+        let files = files
+            .iter()
+            .map(|(name, content)| (*name, content.as_str()))
+            .collect::<Vec<_>>();
+        let flags = [
+            "-std=c++17",
+            "-Wall",
+            "-Wextra",
+            "-Werror",
+            "-o",
+            "main",
+            "main.cpp",
+        ];
+        let Some(dir) = compile("g++", "cpp-parser", &files, &flags) else {
+            return;
+        };
+        assert_eq!(
+            run(&dir, "main", &[], &[]),
+            "ok
+the argument '--json' cannot be used with '--yaml'
+the following required arguments were not provided:
+  --password
+the argument '--fast' cannot be used with '--slow'
+the following required arguments were not provided:
+  <--fast|--slow>
+the following required arguments were not provided:
+  <file>
+"
+        );
+    }
+}
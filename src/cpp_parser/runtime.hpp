@@ -0,0 +1,246 @@
+class ParseError : public std::runtime_error {
+public:
+    explicit ParseError(const std::string& message, std::string usage = {})
+        : std::runtime_error(message), usage_(std::move(usage)) {}
+
+    const std::string& usage() const { return usage_; }
+
+private:
+    std::string usage_;
+};
+
+class HelpRequested : public std::runtime_error {
+public:
+    using std::runtime_error::runtime_error;
+};
+
+namespace detail {
+
+struct OptionSpec {
+    std::vector<char> shorts;
+    std::vector<std::string> longs;
+    bool takes_value;
+};
+
+struct Token {
+    enum class Kind { End, Option, Positional };
+
+    Kind kind = Kind::End;
+    std::size_t option = 0;
+    std::string name;
+    std::optional<std::string> value;
+};
+
+inline bool looks_like_flag(const std::string& arg) {
+    return arg.size() > 1 && arg[0] == '-';
+}
+
+class Lexer {
+public:
+    explicit Lexer(std::vector<std::string> args) : args_(std::move(args)) {}
+
+    const char* usage = "";
+    bool only_positionals = false;
+    bool hyphen_positional = false;
+
+    Token next(const std::vector<OptionSpec>& specs) {
+        if (!shorts_.empty()) {
+            return next_short(specs);
+        }
+        if (pos_ >= args_.size()) {
+            return {};
+        }
+
+        const std::string& arg = args_[pos_++];
+        if (only_positionals || !looks_like_flag(arg)) {
+            return positional(arg);
+        }
+        if (arg == "--") {
+            only_positionals = true;
+            return next(specs);
+        }
+
+        if (arg[1] == '-') {
+            std::size_t eq = arg.find('=');
+            std::string name = arg.substr(2, eq == std::string::npos ? eq : eq - 2);
+            for (std::size_t i = 0; i < specs.size(); ++i) {
+                for (const auto& long_name : specs[i].longs) {
+                    if (long_name == name) {
+                        Token token{Token::Kind::Option, i, "--" + name, std::nullopt};
+                        if (eq != std::string::npos) {
+                            token.value = arg.substr(eq + 1);
+                        }
+                        return token;
+                    }
+                }
+            }
+            if (hyphen_positional) {
+                return positional(arg);
+            }
+            throw ParseError("unexpected argument '" + arg.substr(0, eq) + "' found");
+        }
+
+        if (hyphen_positional && !find_short(specs, arg[1])) {
+            return positional(arg);
+        }
+        shorts_ = arg.substr(1);
+        return next_short(specs);
+    }
+
+    std::vector<std::string> values(const Token& token, std::size_t min, std::size_t max,
+                                    bool allow_hyphen) {
+        std::vector<std::string> values;
+        if (token.value) {
+            values.push_back(*token.value);
+        }
+        while (values.size() < max && !token.value && pos_ < args_.size()) {
+            const std::string& next = args_[pos_];
+            if (next == "--" || (!allow_hyphen && looks_like_flag(next))) {
+                break;
+            }
+            values.push_back(next);
+            ++pos_;
+        }
+
+        if (values.size() < min) {
+            if (min == 1) {
+                throw ParseError("a value is required for '" + token.name +
+                                 "' but none was supplied");
+            }
+            throw ParseError(std::to_string(min) + " values required by '" + token.name +
+                             "'; only " + std::to_string(values.size()) + " were provided");
+        }
+        return values;
+    }
+
+    std::vector<std::string> rest() {
+        std::vector<std::string> rest(args_.begin() + static_cast<std::ptrdiff_t>(pos_),
+                                      args_.end());
+        pos_ = args_.size();
+        return rest;
+    }
+
+private:
+    static Token positional(const std::string& arg) {
+        return Token{Token::Kind::Positional, 0, arg, arg};
+    }
+
+    static const OptionSpec* find_short(const std::vector<OptionSpec>& specs, char c) {
+        for (const auto& spec : specs) {
+            for (char s : spec.shorts) {
+                if (s == c) {
+                    return &spec;
+                }
+            }
+        }
+        return nullptr;
+    }
+
+    Token next_short(const std::vector<OptionSpec>& specs) {
+        char c = shorts_[0];
+        shorts_.erase(0, 1);
+
+        const OptionSpec* spec = find_short(specs, c);
+        if (!spec) {
+            shorts_.clear();
+            throw ParseError(std::string("unexpected argument '-") + c + "' found");
+        }
+
+        Token token{Token::Kind::Option, static_cast<std::size_t>(spec - specs.data()),
+                    std::string("-") + c, std::nullopt};
+        if (spec->takes_value && !shorts_.empty()) {
+            token.value = shorts_[0] == '=' ? shorts_.substr(1) : shorts_;
+            shorts_.clear();
+        }
+        return token;
+    }
+
+    std::vector<std::string> args_;
+    std::size_t pos_ = 0;
+    std::string shorts_;
+};
+
+inline std::vector<std::string> split(const std::string& value, std::optional<char> delimiter) {
+    std::vector<std::string> parts;
+    if (!delimiter) {
+        parts.push_back(value);
+        return parts;
+    }
+
+    std::size_t start = 0;
+    for (std::size_t end; (end = value.find(*delimiter, start)) != std::string::npos;
+         start = end + 1) {
+        parts.push_back(value.substr(start, end - start));
+    }
+    parts.push_back(value.substr(start));
+    return parts;
+}
+
+inline bool from_string(const std::string& value, std::string& out) {
+    out = value;
+    return !value.empty();
+}
+
+inline bool from_string(const std::string& value, std::int64_t& out) {
+    char* end = nullptr;
+    errno = 0;
+    long long parsed = std::strtoll(value.c_str(), &end, 10);
+    if (value.empty() || errno != 0 || *end != '\0') {
+        return false;
+    }
+    out = static_cast<std::int64_t>(parsed);
+    return true;
+}
+
+inline bool from_string(const std::string& value, double& out) {
+    char* end = nullptr;
+    errno = 0;
+    out = std::strtod(value.c_str(), &end);
+    return !value.empty() && errno == 0 && *end == '\0';
+}
+
+inline bool from_string(const std::string& value, bool& out) {
+    if (value == "true") {
+        out = true;
+    } else if (value == "false") {
+        out = false;
+    } else {
+        return false;
+    }
+    return true;
+}
+
+inline bool from_string(const std::string& value, std::filesystem::path& out) {
+    out = value;
+    return !value.empty();
+}
+
+template <typename T>
+T convert(const std::string& value, const std::string& name,
+          const char* possible_values = nullptr) {
+    T out{};
+    if (!from_string(value, out)) {
+        std::string message = "invalid value '" + value + "' for '" + name + "'";
+        if (possible_values) {
+            message += "\n  [possible values: " + std::string(possible_values) + "]";
+        }
+        throw ParseError(message);
+    }
+    return out;
+}
+
+inline std::optional<std::string> env(const char* name) {
+    const char* value = std::getenv(name);
+    if (!value) {
+        return std::nullopt;
+    }
+    return std::string(value);
+}
+
+inline void increment(std::uint8_t& count) {
+    if (count < UINT8_MAX) {
+        ++count;
+    }
+}
+
+}  // namespace detail
@@ -0,0 +1,359 @@
+use crate::{ArgumentDef, CommandDef, OptionAction, OptionDef, PossibleValueDef};
+use clap::Command;
+
+/// A table cell, either plain text or a comma separated list of inline code spans.
+enum Cell {
+    Text(String),
+    Code(Vec<String>),
+}
+
+/// An entry of a bullet list, labelled with an inline code span and optionally linking to an anchor.
+struct Item {
+    code: String,
+    link: Option<String>,
+    description: String,
+}
+
+trait DocWriter {
+    fn heading(&mut self, level: usize, id: &str, text: &str);
+    fn paragraph(&mut self, text: &str);
+    fn code_block(&mut self, code: &str);
+    fn table(&mut self, header: &[&str], rows: &[Vec<Cell>]);
+    fn list(&mut self, items: &[Item]);
+}
+
+#[derive(Default)]
+struct Markdown {
+    out: String,
+}
+
+impl Markdown {
+    fn escape(text: &str) -> String {
+        text.replace('|', "\\|").replace('\n', " ")
+    }
+
+    fn cell(cell: &Cell) -> String {
+        match cell {
+            Cell::Text(text) => Self::escape(text),
+            Cell::Code(spans) => spans
+                .iter()
+                .map(|s| format!("`{}`", Self::escape(s)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+impl DocWriter for Markdown {
+    fn heading(&mut self, level: usize, id: &str, text: &str) {
+        if !id.is_empty() {
+            self.out.push_str(&format!("<a id=\"{id}\"></a>\n\n"));
+        }
+        self.out
+            .push_str(&format!("{} {}\n\n", "#".repeat(level), text));
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        self.out.push_str(&format!("{text}\n\n"));
+    }
+
+    fn code_block(&mut self, code: &str) {
+        self.out.push_str(&format!("```text\n{code}\n```\n\n"));
+    }
+
+    fn table(&mut self, header: &[&str], rows: &[Vec<Cell>]) {
+        self.out.push_str(&format!("| {} |\n", header.join(" | ")));
+        self.out
+            .push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+        for row in rows {
+            let cells = row.iter().map(Self::cell).collect::<Vec<_>>();
+            self.out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        self.out.push('\n');
+    }
+
+    fn list(&mut self, items: &[Item]) {
+        for item in items {
+            let label = match &item.link {
+                Some(link) => format!("[`{}`](#{})", item.code, link),
+                None => format!("`{}`", item.code),
+            };
+            if item.description.is_empty() {
+                self.out.push_str(&format!("- {label}\n"));
+            } else {
+                self.out
+                    .push_str(&format!("- {label} — {}\n", item.description));
+            }
+        }
+        self.out.push('\n');
+    }
+}
+
+#[derive(Default)]
+struct Html {
+    out: String,
+}
+
+impl Html {
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn cell(cell: &Cell) -> String {
+        match cell {
+            Cell::Text(text) => Self::escape(text),
+            Cell::Code(spans) => spans
+                .iter()
+                .map(|s| format!("<code>{}</code>", Self::escape(s)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    fn finish(self, title: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+             body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n\
+             table {{ border-collapse: collapse; }}\n\
+             th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }}\n\
+             pre {{ background: #f4f4f4; padding: 0.6em; }}\n\
+             </style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            Self::escape(title),
+            self.out
+        )
+    }
+}
+
+impl DocWriter for Html {
+    fn heading(&mut self, level: usize, id: &str, text: &str) {
+        let id = if id.is_empty() {
+            String::new()
+        } else {
+            format!(" id=\"{}\"", Self::escape(id))
+        };
+        self.out.push_str(&format!(
+            "<h{level}{id}>{}</h{level}>\n",
+            Self::escape(text)
+        ));
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        self.out
+            .push_str(&format!("<p>{}</p>\n", Self::escape(text)));
+    }
+
+    fn code_block(&mut self, code: &str) {
+        self.out
+            .push_str(&format!("<pre><code>{}</code></pre>\n", Self::escape(code)));
+    }
+
+    fn table(&mut self, header: &[&str], rows: &[Vec<Cell>]) {
+        self.out.push_str("<table>\n<tr>");
+        for h in header {
+            self.out.push_str(&format!("<th>{}</th>", Self::escape(h)));
+        }
+        self.out.push_str("</tr>\n");
+        for row in rows {
+            self.out.push_str("<tr>");
+            for cell in row {
+                self.out.push_str(&format!("<td>{}</td>", Self::cell(cell)));
+            }
+            self.out.push_str("</tr>\n");
+        }
+        self.out.push_str("</table>\n");
+    }
+
+    fn list(&mut self, items: &[Item]) {
+        self.out.push_str("<ul>\n");
+        for item in items {
+            let code = format!("<code>{}</code>", Self::escape(&item.code));
+            let label = match &item.link {
+                Some(link) => format!("<a href=\"#{}\">{}</a>", Self::escape(link), code),
+                None => code,
+            };
+            if item.description.is_empty() {
+                self.out.push_str(&format!("<li>{label}</li>\n"));
+            } else {
+                self.out.push_str(&format!(
+                    "<li>{label} — {}</li>\n",
+                    Self::escape(&item.description)
+                ));
+            }
+        }
+        self.out.push_str("</ul>\n");
+    }
+}
+
+fn value_names(possible_values: &[PossibleValueDef], value_type: &Option<String>) -> Cell {
+    if !possible_values.is_empty() {
+        return Cell::Code(
+            possible_values
+                .iter()
+                .filter(|v| !v.is_hidden())
+                .map(|v| v.value().to_string())
+                .collect(),
+        );
+    }
+    Cell::Text(value_type.clone().unwrap_or_default())
+}
+
+fn value_items(possible_values: &[PossibleValueDef]) -> Vec<Item> {
+    possible_values
+        .iter()
+        .filter(|v| !v.is_hidden())
+        .map(|v| Item {
+            code: v.value().to_string(),
+            link: None,
+            description: v.description().unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
+fn describe(description: &str, required: bool, env: &Option<String>) -> Cell {
+    let mut text = description.to_string();
+    if required {
+        text.push_str(" (required)");
+    }
+    if let Some(env) = env {
+        text.push_str(&format!(" (env: {env})"));
+    }
+    Cell::Text(text)
+}
+
+fn option_flags(option: &OptionDef) -> Vec<String> {
+    let mut flags = Vec::new();
+    flags.extend(option.short.iter().map(|s| format!("-{s}")));
+    flags.extend(option.visible_short_aliases.iter().map(|s| format!("-{s}")));
+    flags.extend(option.long().map(|l| format!("--{l}")));
+    flags.extend(option.visible_aliases.iter().map(|l| format!("--{l}")));
+    flags
+}
+
+fn document_command(writer: &mut dyn DocWriter, def: &CommandDef, cmd: &Command, path: &[&str]) {
+    let id = path.join("-");
+    writer.heading(2, &id, &path.join(" "));
+
+    if !def.description.is_empty() {
+        writer.paragraph(&def.description);
+    }
+
+    let usage = cmd.clone().render_usage().to_string();
+    writer.code_block(usage.trim_start_matches("Usage: ").trim());
+
+    if !def.visible_aliases.is_empty() {
+        writer.paragraph(&format!("Aliases: {}", def.visible_aliases.join(", ")));
+    }
+
+    let arguments = def
+        .arguments
+        .iter()
+        .filter(|a| !a.hide)
+        .collect::<Vec<&ArgumentDef>>();
+    if !arguments.is_empty() {
+        writer.heading(3, "", "Arguments");
+        let rows = arguments
+            .iter()
+            .map(|arg| {
+                vec![
+                    Cell::Code(vec![format!("<{}>", arg.name)]),
+                    describe(&arg.description, arg.required, &arg.env),
+                    value_names(&arg.possible_values, &arg.value_type),
+                    Cell::Text(arg.default_value.clone().unwrap_or_default()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        writer.table(&["Argument", "Description", "Values", "Default"], &rows);
+    }
+
+    let options = def
+        .options
+        .iter()
+        .filter(|o| !o.hide)
+        .collect::<Vec<&OptionDef>>();
+    if !options.is_empty() {
+        writer.heading(3, "", "Options");
+        let rows = options
+            .iter()
+            .map(|option| {
+                let values = match option.action {
+                    OptionAction::Flag | OptionAction::Count => Cell::Text(String::new()),
+                    OptionAction::Set | OptionAction::Append => {
+                        value_names(&option.possible_values, &option.value_type)
+                    }
+                };
+                vec![
+                    Cell::Code(option_flags(option)),
+                    describe(&option.description, option.required, &option.env),
+                    values,
+                    Cell::Text(option.default_value.clone().unwrap_or_default()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        writer.table(&["Option", "Description", "Values", "Default"], &rows);
+    }
+
+    let described = arguments
+        .iter()
+        .map(|a| (format!("<{}>", a.name), &a.possible_values))
+        .chain(
+            options
+                .iter()
+                .map(|o| (option_flags(o).join(", "), &o.possible_values)),
+        )
+        .filter(|(_, values)| values.iter().any(|v| v.description().is_some()));
+    for (name, values) in described {
+        writer.paragraph(&format!("Values for {name}:"));
+        writer.list(&value_items(values));
+    }
+
+    let subcommands = def
+        .subcommands
+        .iter()
+        .filter(|s| !s.hidden)
+        .collect::<Vec<&CommandDef>>();
+    if !subcommands.is_empty() {
+        writer.heading(3, "", "Subcommands");
+        let items = subcommands
+            .iter()
+            .map(|sub| Item {
+                code: sub.name.clone(),
+                link: Some(format!("{}-{}", id, sub.name)),
+                description: sub.description.clone(),
+            })
+            .collect::<Vec<_>>();
+        writer.list(&items);
+    }
+
+    for sub in subcommands {
+        let sub_cmd = cmd
+            .find_subcommand(&sub.name)
+            .expect("Subcommand missing from built command");
+        let mut sub_path = path.to_vec();
+        sub_path.push(&sub.name);
+        document_command(writer, sub, sub_cmd, &sub_path);
+    }
+}
+
+/// Renders a reference for `def` and all of its visible subcommands, either as Markdown or as a
+/// standalone HTML page.
+pub fn generate_docs(def: &CommandDef, command: &Command, html: bool) -> String {
+    // Building the command fills in the `mytool sub` bin names used in the usage lines.
+    let mut cmd = command.clone();
+    cmd.build();
+
+    let title = format!("{} reference", def.name);
+    if html {
+        let mut writer = Html::default();
+        writer.heading(1, "", &title);
+        document_command(&mut writer, def, &cmd, &[&def.name]);
+        writer.finish(&title)
+    } else {
+        let mut writer = Markdown::default();
+        writer.heading(1, "", &title);
+        document_command(&mut writer, def, &cmd, &[&def.name]);
+        writer.out
+    }
+}
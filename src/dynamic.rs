@@ -0,0 +1,472 @@
+use crate::{CommandDef, OptionAction, leak_string, shell::CompletionShell};
+use clap::{
+    Arg, Command,
+    builder::{NonEmptyStringValueParser, PossibleValuesParser},
+};
+
+const PREFIX: &str = "__clapper_dynamic_";
+const SUFFIX: &str = "__";
+
+fn placeholder(command: &[String]) -> String {
+    let json = serde_json::to_string(command).unwrap();
+    let hex = json.bytes().map(|b| format!("{b:02x}")).collect::<String>();
+    format!("{PREFIX}{hex}{SUFFIX}")
+}
+
+/// Decodes the placeholder at the start of `text`, returning the command and the placeholder length.
+fn decode(text: &str) -> Option<(Vec<String>, usize)> {
+    let hex = text.strip_prefix(PREFIX)?;
+    let hex_len = hex.find(SUFFIX)?;
+    let bytes = (0..hex_len)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let command = serde_json::from_slice(&bytes).ok()?;
+    Some((command, PREFIX.len() + hex_len + SUFFIX.len()))
+}
+
+/// The command of `arg` if its only possible value is a placeholder.
+pub fn arg_command(arg: &Arg) -> Option<Vec<String>> {
+    match arg.get_possible_values().as_slice() {
+        [value] => decode(value.get_name()).map(|(command, _)| command),
+        _ => None,
+    }
+}
+
+/// Replaces every placeholder in `text` that is surrounded by `before` and `after`, including
+/// those, with `render(command)`.
+fn replace(text: &str, before: &str, after: &str, render: impl Fn(&[String]) -> String) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PREFIX) {
+        let Some((command, len)) = decode(&rest[start..]) else {
+            out.push_str(&rest[..start + PREFIX.len()]);
+            rest = &rest[start + PREFIX.len()..];
+            continue;
+        };
+        let end = start + len;
+        if rest[..start].ends_with(before) && rest[end..].starts_with(after) {
+            out.push_str(&rest[..start - before.len()]);
+            out.push_str(&render(&command));
+            rest = &rest[end + after.len()..];
+        } else {
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Quotes `arg` for POSIX shells.
+fn sh_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Quotes `arg` with double quotes, escaping `\`, `"`, `$` and backticks.
+fn double_quote(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn join(command: &[String], quote: fn(&str) -> String) -> String {
+    command
+        .iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Gives every visible argument of `def` with a `complete_command` a placeholder possible value
+/// encoding the command.
+///
+/// The static generators only know about possible values, so this makes them emit a spot for the
+/// values which `resolve_script` and friends then replace with a shell specific call of the command.
+pub fn add_placeholders(mut command: Command, def: &CommandDef) -> Command {
+    for option in &def.options {
+        let takes_value = matches!(option.action, OptionAction::Set | OptionAction::Append);
+        if option.hide || !takes_value || option.complete_command.is_empty() {
+            continue;
+        }
+        let value = leak_string(placeholder(&option.complete_command));
+        command = command.mut_arg(&option.id, |arg| {
+            arg.value_parser(PossibleValuesParser::new([value]))
+        });
+    }
+    for argument in &def.arguments {
+        if argument.hide || argument.complete_command.is_empty() {
+            continue;
+        }
+        let value = leak_string(placeholder(&argument.complete_command));
+        command = command.mut_arg(&argument.name, |arg| {
+            arg.value_parser(PossibleValuesParser::new([value]))
+        });
+    }
+    for sub in def.subcommands.iter().filter(|s| !s.hidden) {
+        command = command.mut_subcommand(&sub.name, |c| add_placeholders(c, sub));
+    }
+    command
+}
+
+/// Turns placeholder possible values back into free form values, for outputs describing the
+/// command rather than completing it.
+pub fn remove_placeholders(mut command: Command) -> Command {
+    let dynamic = command
+        .get_arguments()
+        .filter(|a| arg_command(a).is_some())
+        .map(|a| a.get_id().clone())
+        .collect::<Vec<_>>();
+    for id in dynamic {
+        command = command.mut_arg(id, |arg| arg.value_parser(NonEmptyStringValueParser::new()));
+    }
+    let subcommands = command
+        .get_subcommands()
+        .map(|s| s.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in subcommands {
+        command = command.mut_subcommand(name, remove_placeholders);
+    }
+    command
+}
+
+/// Whether `command` or any of its subcommands has a dynamic argument.
+fn has_dynamic(command: &Command) -> bool {
+    command.get_arguments().any(|a| arg_command(a).is_some())
+        || command.get_subcommands().any(has_dynamic)
+}
+
+fn zsh_helper(bin: &str) -> String {
+    format!(
+        "(( $+functions[_{bin}__dynamic_values] )) ||
+_{bin}__dynamic_values() {{
+    local -a candidates
+    candidates=(\"${{(@f)$(\"$@\" \"$PREFIX\" 2>/dev/null)}}\")
+    candidates=(${{candidates:#}})
+    candidates=(\"${{(@)candidates//:/\\\\:}}\")
+    candidates=(\"${{(@)candidates/$'\\t'/:}}\")
+    _describe -t values 'values' candidates
+}}
+
+"
+    )
+}
+
+/// The `complete` lines for dynamic positionals, which the fish generator does not complete,
+/// using the same conditions as the generated script. Those stop at two subcommand levels, deeper
+/// ones add a `__fish_seen_subcommand_from` per level.
+fn fish_positionals(
+    bin: &str,
+    parents: &[&str],
+    command: &Command,
+    root_has_subcommands: bool,
+    out: &mut String,
+) {
+    let name = bin.replace('-', "_");
+    let mut conditions = Vec::new();
+    match parents {
+        [] if command.has_subcommands() => conditions.push(format!("__fish_{name}_needs_command")),
+        [] => {}
+        [parent, rest @ ..] => {
+            if root_has_subcommands {
+                conditions.push(format!("__fish_{name}_using_subcommand {parent}"));
+            } else {
+                conditions.push(format!("__fish_seen_subcommand_from {parent}"));
+            }
+            for sub in rest {
+                conditions.push(format!("__fish_seen_subcommand_from {sub}"));
+            }
+            if command.has_subcommands() {
+                let subs = command
+                    .get_subcommands()
+                    .flat_map(Command::get_name_and_visible_aliases)
+                    .collect::<Vec<_>>();
+                conditions.push(format!(
+                    "not __fish_seen_subcommand_from {}",
+                    subs.join(" ")
+                ));
+            }
+        }
+    }
+    let condition = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" -n \"{}\"", conditions.join("; and "))
+    };
+
+    for arg in command.get_positionals() {
+        if let Some(complete) = arg_command(arg) {
+            out.push_str(&format!(
+                "complete -c {bin}{condition} -f -a \"{}\"\n",
+                fish_call(&complete)
+            ));
+        }
+    }
+
+    for sub in command.get_subcommands() {
+        for sub_name in sub.get_name_and_visible_aliases() {
+            let mut parents = parents.to_vec();
+            parents.push(sub_name);
+            fish_positionals(bin, &parents, sub, root_has_subcommands, out);
+        }
+    }
+}
+
+/// `(command args (commandline -ct))`, escaped for use inside a double quoted fish string.
+fn fish_call(command: &[String]) -> String {
+    let fish_quote = |arg: &str| format!("'{}'", arg.replace('\\', "\\\\").replace('\'', "\\'"));
+    let call = format!("({} (commandline -ct))", join(command, fish_quote));
+    call.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+}
+
+fn nushell_call(command: &[String]) -> String {
+    let nu_quote = |arg: &str| format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""));
+    format!(
+        "^{} ($context | split row ' ' | last) | lines | each {{|line| \
+         let parts = ($line | split row \"\\t\"); \
+         if ($parts | length) > 1 {{ {{value: $parts.0, description: $parts.1}} }} else {{ $parts.0 }} }}",
+        join(command, nu_quote)
+    )
+}
+
+/// Fails when a placeholder survived in `output`, which happens when the generator's quoting no
+/// longer matches the one expected by `resolve_script` or `resolve_fig`.
+fn check_resolved(output: String, what: &str) -> Result<String, String> {
+    let Some(at) = output.find(PREFIX) else {
+        return Ok(output);
+    };
+    let command = decode(&output[at..])
+        .map(|(command, _)| format!(" {}", command.join(" ")))
+        .unwrap_or_default();
+    Err(format!(
+        "unable to wire up the complete_command{command} in the {what}, the output of the \
+         generator is not in the expected format"
+    ))
+}
+
+/// Replaces the placeholders in the completion script for `shell` with calls to the commands.
+pub fn resolve_script(
+    shell: CompletionShell,
+    command: &Command,
+    script: String,
+) -> Result<String, String> {
+    if !has_dynamic(command) {
+        return Ok(script);
+    }
+    let bin = command.get_name();
+
+    let script = match shell {
+        CompletionShell::Bash => replace(&script, "", "", |complete| {
+            format!(
+                "$({} \"${{cur}}\" 2>/dev/null | cut -f1)",
+                join(complete, sh_quote)
+            )
+        }),
+        CompletionShell::Zsh => {
+            let script = replace(&script, "(", ")", |complete| {
+                let args = join(complete, double_quote).replace('\'', "'\\''");
+                format!("{{_{bin}__dynamic_values {args}}}")
+            });
+            let at = script
+                .rfind("if [ \"$funcstack[1]\"")
+                .unwrap_or(script.len());
+            format!("{}{}{}", &script[..at], zsh_helper(bin), &script[at..])
+        }
+        CompletionShell::Fish => {
+            let mut script = replace(&script, "\"", "\\t''\"", |complete| {
+                format!("\"{}\"", fish_call(complete))
+            });
+            fish_positionals(bin, &[], command, command.has_subcommands(), &mut script);
+            script
+        }
+        CompletionShell::Nushell => {
+            // Completers receive the command line so far when they declare a parameter for it.
+            let lines = script.lines().collect::<Vec<_>>();
+            let mut declared = String::new();
+            for (i, line) in lines.iter().enumerate() {
+                let next_is_dynamic = lines.get(i + 1).is_some_and(|l| l.contains(PREFIX));
+                match line.strip_suffix("[] {") {
+                    Some(head) if next_is_dynamic => {
+                        declared.push_str(head);
+                        declared.push_str("[context: string] {");
+                    }
+                    _ => declared.push_str(line),
+                }
+                declared.push('\n');
+            }
+            replace(&declared, "[ \"", "\" ]", nushell_call)
+        }
+        CompletionShell::PowerShell | CompletionShell::Elvish => script,
+    };
+    check_resolved(script, &format!("{shell} script"))
+}
+
+/// Replaces the placeholder suggestions in a Fig spec with generators running the commands.
+pub fn resolve_fig(spec: String) -> Result<String, String> {
+    if !spec.contains(PREFIX) {
+        return Ok(spec);
+    }
+
+    let lines = spec.lines().collect::<Vec<_>>();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let dynamic = lines.get(i + 1).and_then(|l| {
+            let value = l.trim().strip_prefix('"')?.strip_suffix("\",")?;
+            decode(value).map(|(command, _)| command)
+        });
+        match dynamic {
+            Some(command)
+                if lines[i].trim() == "suggestions: ["
+                    && lines.get(i + 2).is_some_and(|l| l.trim() == "],") =>
+            {
+                let indent = &lines[i][..lines[i].len() - lines[i].trim_start().len()];
+                let script = command
+                    .iter()
+                    .map(|arg| serde_json::to_string(arg).unwrap())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                out.push(format!("{indent}generators: {{"));
+                out.push(format!(
+                    "{indent}  script: (tokens) => [{script}, tokens[tokens.length - 1]],"
+                ));
+                out.push(format!(
+                    "{indent}  postProcess: (out) => out.split(\"\\n\").filter((line) => line).map((line) => {{"
+                ));
+                out.push(format!(
+                    "{indent}    const [name, description] = line.split(\"\\t\");"
+                ));
+                out.push(format!("{indent}    return {{ name, description }};"));
+                out.push(format!("{indent}  }}),"));
+                out.push(format!("{indent}}},"));
+                i += 3;
+            }
+            _ => {
+                out.push(lines[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    check_resolved(out.join("\n") + "\n", "Fig spec")
+}
+
+/// The carapace action running `command`, which provides the current value as `C_VALUE`.
+pub fn carapace_action(command: &[String]) -> String {
+    format!("$({} \"${{C_VALUE}}\")", join(command, sh_quote))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CliDef, generate_completion, generate_script, make_command};
+
+    /// A root option and a positional three subcommands deep, both completed by commands.
+    fn command() -> Command {
+        let spec = serde_json::json!({
+            "command": {
+                "name": "t",
+                "description": "t",
+                "options": [
+                    { "id": "profile", "description": "p", "complete_command": ["lister", "it's"] }
+                ],
+                "subcommands": [{
+                    "name": "a",
+                    "description": "a",
+                    "subcommands": [{
+                        "name": "b",
+                        "description": "b",
+                        "subcommands": [{
+                            "name": "c",
+                            "description": "c",
+                            "arguments": [
+                                { "name": "x", "description": "x", "complete_command": ["lister", "x"] }
+                            ]
+                        }]
+                    }]
+                }]
+            }
+        });
+        let def = serde_json::from_value::<CliDef>(spec).unwrap().command;
+        add_placeholders(make_command(&def, &[]), &def)
+    }
+
+    fn script(shell: CompletionShell) -> String {
+        let script = String::from_utf8(generate_script(shell, &command()).unwrap()).unwrap();
+        assert!(!script.contains(PREFIX), "{script}");
+        script
+    }
+
+    #[test]
+    fn bash_calls_the_command() {
+        let script = script(CompletionShell::Bash);
+        assert!(script.contains(
+            r#"COMPREPLY=($(compgen -W "$('lister' 'it'\''s' "${cur}" 2>/dev/null | cut -f1)" -- "${cur}"))"#
+        ));
+        assert!(script.contains(r#"opts="$('lister' 'x' "${cur}" 2>/dev/null | cut -f1)""#));
+    }
+
+    #[test]
+    fn zsh_calls_the_helper() {
+        let script = script(CompletionShell::Zsh);
+        assert!(script.contains(r#"'--profile=[p]: :{_t__dynamic_values "lister" "it'\''s"}' \"#));
+        assert!(script.contains(r#"'::x -- x:{_t__dynamic_values "lister" "x"}' \"#));
+        assert!(script.contains("_t__dynamic_values() {"));
+    }
+
+    #[test]
+    fn fish_completes_positionals_at_any_depth() {
+        let script = script(CompletionShell::Fish);
+        assert!(script.contains(
+            r#"complete -c t -n "__fish_t_needs_command" -l profile -d 'p' -r -f -a "('lister' 'it\\'s' (commandline -ct))""#
+        ));
+        assert!(script.contains(
+            r#"complete -c t -n "__fish_t_using_subcommand a; and __fish_seen_subcommand_from b; and __fish_seen_subcommand_from c" -f -a "('lister' 'x' (commandline -ct))""#
+        ));
+    }
+
+    #[test]
+    fn nushell_passes_the_context() {
+        let script = script(CompletionShell::Nushell);
+        assert!(script.contains(r#"def "nu-complete t profile" [context: string] {"#));
+        assert!(
+            script.contains(r#"    ^"lister" "it's" ($context | split row ' ' | last) | lines"#)
+        );
+        assert!(script.contains(r#"def "nu-complete t a b c x" [context: string] {"#));
+    }
+
+    #[test]
+    fn powershell_and_elvish_have_no_placeholders() {
+        script(CompletionShell::PowerShell);
+        script(CompletionShell::Elvish);
+    }
+
+    #[test]
+    fn fig_uses_generators() {
+        let spec = generate_completion(clap_complete_fig::Fig, &command());
+        let spec = resolve_fig(String::from_utf8(spec).unwrap()).unwrap();
+        assert!(!spec.contains(PREFIX));
+        assert!(
+            spec.contains(r#"script: (tokens) => ["lister", "it's", tokens[tokens.length - 1]],"#)
+        );
+        assert!(
+            spec.contains(r#"script: (tokens) => ["lister", "x", tokens[tokens.length - 1]],"#)
+        );
+    }
+
+    #[test]
+    fn leftover_placeholders_fail() {
+        let script = format!("opts=\"{}\"", placeholder(&["lister".to_string()]));
+        let error = check_resolved(script, "bash script").unwrap_err();
+        assert!(
+            error.starts_with("unable to wire up the complete_command lister in the bash script")
+        );
+    }
+}
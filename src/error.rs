@@ -0,0 +1,277 @@
+use crate::check::Problem;
+use serde_json::json;
+use std::{fmt, io, ops::Range, path::PathBuf};
+
+/// A position in the spec, both one-based.
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    fn from_offset(input: &str, offset: usize) -> Location {
+        let before = &input[..offset.min(input.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// An error reported to the user instead of a panic.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the spec or writing an output failed.
+    Io {
+        path: PathBuf,
+        action: &'static str,
+        source: io::Error,
+    },
+    /// The spec is not valid JSON, YAML or TOML, or does not match the expected structure.
+    Parse {
+        path: PathBuf,
+        format: &'static str,
+        message: String,
+        location: Option<Location>,
+        snippet: Option<String>,
+    },
+    /// The spec parsed but is inconsistent, e.g. it references an unknown argument.
+    Spec {
+        path: PathBuf,
+        problems: Vec<Problem>,
+    },
+    /// The command line options cannot be combined.
+    Usage(String),
+    /// An output could not be generated from a valid spec.
+    Generate(String),
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, action: &'static str) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io {
+            path,
+            action,
+            source,
+        }
+    }
+
+    /// A parse error at `location` in `input`.
+    fn parse(
+        path: PathBuf,
+        format: &'static str,
+        input: &str,
+        message: String,
+        location: Option<Location>,
+    ) -> Error {
+        // serde_json and serde_norway append the position to the message, it is reported separately.
+        let message = match message.find(" at line ") {
+            Some(at) if location.is_some() => message[..at].to_string(),
+            _ => message,
+        };
+        let snippet = location
+            .and_then(|l| input.lines().nth(l.line - 1))
+            .map(str::to_string);
+        Error::Parse {
+            path,
+            format,
+            message,
+            location,
+            snippet,
+        }
+    }
+
+    pub fn json(path: PathBuf, input: &str, error: serde_json::Error) -> Error {
+        // serde_json counts the column in bytes, the other formats and the caret in characters.
+        let location = (error.line() > 0).then(|| {
+            let line = input.lines().nth(error.line() - 1).unwrap_or_default();
+            let bytes = error.column().saturating_sub(1);
+            Location {
+                line: error.line(),
+                column: line
+                    .char_indices()
+                    .take_while(|(i, c)| i + c.len_utf8() <= bytes)
+                    .count()
+                    + 1,
+            }
+        });
+        Error::parse(path, "JSON", input, error.to_string(), location)
+    }
+
+    pub fn yaml(path: PathBuf, input: &str, error: serde_norway::Error) -> Error {
+        let location = error
+            .location()
+            .map(|l| Location::from_offset(input, l.index()));
+        Error::parse(path, "YAML", input, error.to_string(), location)
+    }
+
+    pub fn toml(path: PathBuf, input: &str, error: toml::de::Error) -> Error {
+        let location = error
+            .span()
+            .map(|Range { start, .. }| Location::from_offset(input, start));
+        Error::parse(path, "TOML", input, error.message().to_string(), location)
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::Io { .. } => "io",
+            Error::Parse { .. } => "parse",
+            Error::Spec { .. } => "spec",
+            Error::Usage(_) => "usage",
+            Error::Generate(_) => "generate",
+        }
+    }
+
+    /// Prints the error to stderr, as one JSON object per line when `json` is set.
+    pub fn report(&self, json: bool) {
+        if !json {
+            eprintln!("{self}");
+            return;
+        }
+
+        let file = match self {
+            Error::Io { path, .. } | Error::Parse { path, .. } | Error::Spec { path, .. } => {
+                Some(path.display().to_string())
+            }
+            Error::Usage(_) | Error::Generate(_) => None,
+        };
+        let diagnostic = |level: &str, message: String, json_path: Option<&str>| {
+            let (location, snippet) = match self {
+                Error::Parse {
+                    location, snippet, ..
+                } => (*location, snippet.as_deref()),
+                _ => (None, None),
+            };
+            json!({
+                "level": level,
+                "kind": self.kind(),
+                "message": message,
+                "file": file,
+                "json_path": json_path,
+                "line": location.map(|l| l.line),
+                "column": location.map(|l| l.column),
+                "snippet": snippet,
+            })
+        };
+
+        match self {
+            Error::Spec { problems, .. } => {
+                for problem in problems {
+                    let level = problem.severity.to_string();
+                    let message = problem.message.clone();
+                    eprintln!("{}", diagnostic(&level, message, Some(&problem.path)));
+                }
+            }
+            // The file is reported on its own.
+            Error::Parse {
+                format, message, ..
+            } => eprintln!(
+                "{}",
+                diagnostic("error", format!("invalid {format}: {message}"), None)
+            ),
+            _ => eprintln!("{}", diagnostic("error", self.headline(), None)),
+        }
+    }
+
+    fn headline(&self) -> String {
+        match self {
+            Error::Io {
+                path,
+                action,
+                source,
+            } => format!("unable to {action} {}: {source}", path.display()),
+            Error::Parse {
+                path,
+                format,
+                message,
+                ..
+            } => format!("invalid {format} in {}: {message}", path.display()),
+            Error::Spec { path, problems } => {
+                format!("{} problem(s) in {}", problems.len(), path.display())
+            }
+            Error::Usage(message) | Error::Generate(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Error::Spec { path, problems } = self {
+            let lines = problems.iter().map(|problem| {
+                format!(
+                    "{}: {}: {}: {}",
+                    problem.severity,
+                    path.display(),
+                    problem.path,
+                    problem.message
+                )
+            });
+            return f.write_str(&lines.collect::<Vec<_>>().join("\n"));
+        }
+
+        write!(f, "error: {}", self.headline())?;
+        if let Error::Parse {
+            path,
+            location: Some(location),
+            snippet,
+            ..
+        } = self
+        {
+            write!(
+                f,
+                "\n --> {}:{}:{}",
+                path.display(),
+                location.line,
+                location.column
+            )?;
+            if let Some(snippet) = snippet {
+                let number = location.line.to_string();
+                let gutter = " ".repeat(number.len());
+                let caret = snippet
+                    .chars()
+                    .take(location.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                write!(f, "\n{gutter} |\n{number} | {snippet}\n{gutter} | {caret}^")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_location(input: &str) -> (usize, usize) {
+        let error = serde_json::from_str::<serde_json::Value>(input).unwrap_err();
+        match Error::json(PathBuf::from("spec.json"), input, error) {
+            Error::Parse {
+                location: Some(location),
+                ..
+            } => (location.line, location.column),
+            error => panic!("unexpected {error:?}"),
+        }
+    }
+
+    #[test]
+    fn json_columns_count_characters() {
+        assert_eq!(json_location("{\n  \"a\": x\n}"), (2, 8));
+        assert_eq!(json_location("{\n  \"äöü\": x\n}"), (2, 10));
+        assert_eq!(
+            Location::from_offset("{\n  \"äöü\": x\n}", "{\n  \"äöü\": ".len()).column,
+            10
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use crate::codegen::cpp_string;
+
+const HELPER: &str = include_str!("install/helper.hpp");
+
+/// Headers needed by the install helper in addition to the ones of the completion header.
+pub const INCLUDES: &[&str] = &[
+    "cstdlib",
+    "filesystem",
+    "fstream",
+    "iostream",
+    "system_error",
+];
+
+/// The `detect_shell`, `completion_install_path` and `install_completions` functions for `bin`,
+/// reading the scripts from the completion map called `completions`.
+pub fn generate_install_helper(bin: &str, completions: &str) -> String {
+    HELPER
+        .replace("@BIN@", &cpp_string(bin))
+        .replace("@COMPLETIONS@", completions)
+}
@@ -0,0 +1,130 @@
+/// Detects the user's shell from the parent process (on Linux) or `$SHELL`, returning its name as
+/// used by the completion map (`bash`, `zsh`, `fish`, `powershell`, `elvish` or `nushell`), or an
+/// empty string when it is not recognised.
+inline std::string detect_shell() {
+    auto normalize = [](std::string name) -> std::string {
+        std::size_t slash = name.find_last_of('/');
+        if (slash != std::string::npos) {
+            name = name.substr(slash + 1);
+        }
+        // Login shells are started as e.g. `-bash`.
+        if (!name.empty() && name[0] == '-') {
+            name = name.substr(1);
+        }
+        if (name == "bash" || name == "zsh" || name == "fish" || name == "elvish") {
+            return name;
+        }
+        if (name == "pwsh" || name == "powershell") {
+            return "powershell";
+        }
+        if (name == "nu" || name == "nushell") {
+            return "nushell";
+        }
+        return {};
+    };
+
+#ifdef __linux__
+    std::ifstream comm("/proc/" + std::to_string(getppid()) + "/comm");
+    std::string parent;
+    if (std::getline(comm, parent)) {
+        std::string shell = normalize(parent);
+        if (!shell.empty()) {
+            return shell;
+        }
+    }
+#endif
+
+    const char* shell = std::getenv("SHELL");
+    return shell ? normalize(shell) : std::string();
+}
+
+/// The conventional per-user location of the completion script for `shell`, or an empty path for
+/// unknown shells.
+inline std::filesystem::path completion_install_path(const std::string& shell) {
+    const std::string bin = @BIN@;
+    const char* home_env = std::getenv("HOME");
+    std::filesystem::path home = home_env ? home_env : "";
+    auto xdg_dir = [&home](const char* name, const char* fallback) -> std::filesystem::path {
+        const char* value = std::getenv(name);
+        if (value && *value) {
+            return value;
+        }
+        return home / fallback;
+    };
+    std::filesystem::path data = xdg_dir("XDG_DATA_HOME", ".local/share");
+    std::filesystem::path config = xdg_dir("XDG_CONFIG_HOME", ".config");
+
+    if (shell == "bash") {
+        return data / "bash-completion" / "completions" / bin;
+    }
+    if (shell == "zsh") {
+        return home / ".zfunc" / ("_" + bin);
+    }
+    if (shell == "fish") {
+        return config / "fish" / "completions" / (bin + ".fish");
+    }
+    if (shell == "powershell") {
+        return config / "powershell" / (bin + ".ps1");
+    }
+    if (shell == "elvish") {
+        return config / "elvish" / "lib" / (bin + ".elv");
+    }
+    if (shell == "nushell") {
+        return config / "nushell" / "completions" / (bin + ".nu");
+    }
+    return {};
+}
+
+/// Writes the completion script for `shell`, detected with `detect_shell` when empty, to
+/// `completion_install_path` and prints what to add to the shell's startup file to `out`.
+///
+/// Returns false, after printing the reason, when the shell is unknown or not embedded or the file
+/// cannot be written.
+inline bool install_completions(std::string shell = {}, std::ostream& out = std::cout) {
+    if (shell.empty()) {
+        shell = detect_shell();
+    }
+    if (shell.empty()) {
+        out << "Unable to detect the current shell, please name it explicitly.\n";
+        return false;
+    }
+
+    auto script = @COMPLETIONS@.find(shell);
+    if (script == @COMPLETIONS@.end()) {
+        out << "Completions for " << shell << " are not available.\n";
+        return false;
+    }
+
+    std::filesystem::path path = completion_install_path(shell);
+    std::error_code error;
+    std::filesystem::create_directories(path.parent_path(), error);
+    std::ofstream file(path, std::ios::binary);
+    file.write(reinterpret_cast<const char*>(script->second.data()),
+               static_cast<std::streamsize>(script->second.size()));
+    file.close();
+    if (!file) {
+        out << "Unable to write " << path.string() << "\n";
+        return false;
+    }
+
+    out << "Installed " << shell << " completions to " << path.string() << "\n";
+    if (shell == "bash") {
+        out << "They are loaded automatically by bash-completion. Without it, add this line to "
+               "~/.bashrc:\n    source \""
+            << path.string() << "\"\n";
+    } else if (shell == "zsh") {
+        out << "Add these lines to ~/.zshrc unless ~/.zfunc is already in your fpath:\n"
+               "    fpath=(~/.zfunc $fpath)\n"
+               "    autoload -Uz compinit && compinit\n";
+    } else if (shell == "fish") {
+        out << "They are loaded automatically by new fish sessions.\n";
+    } else if (shell == "powershell") {
+        out << "Add this line to your profile ($PROFILE):\n    . \"" << path.string() << "\"\n";
+    } else if (shell == "elvish") {
+        out << "Add this line to ~/.config/elvish/rc.elv:\n    use " << @BIN@ << "\n";
+    } else if (shell == "nushell") {
+        out << "Add this line to your config.nu ($nu.config-path):\n    source \""
+            << path.string() << "\"\n";
+    }
+    return true;
+}
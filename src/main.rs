@@ -1,55 +1,283 @@
+mod c_header;
+mod carapace;
+mod check;
+mod codegen;
+mod cpp_complete;
+mod cpp_header;
+mod cpp_parser;
+mod docs;
+mod dynamic;
+mod error;
+mod install;
+mod man;
+mod schema;
+mod shell;
+
+use check::{Problem, Severity};
 use clap::{
-    Command,
-    builder::{NonEmptyStringValueParser, ValueParser},
+    ArgGroup, Command, ValueHint,
+    builder::{
+        NonEmptyStringValueParser, PossibleValue, PossibleValuesParser, Resettable, ValueParser,
+        ValueRange,
+    },
     command, value_parser,
 };
-use clap_complete::Shell;
-use serde::{Deserialize, Serialize};
-use std::{fs, io::BufWriter, io::Write, path::PathBuf};
+use clap_complete::Generator;
+use codegen::Encoding;
+use cpp_header::CppHeaderOptions;
+use error::Error;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use shell::CompletionShell;
+use std::{
+    fs,
+    io::BufWriter,
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Number of values an argument takes, either exact (`2`) or a range (`"1.."`, `"2..=5"`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum NumArgs {
+    Exact(usize),
+    Range(String),
+}
 
+/// A possible value, either a bare string or an object carrying a description for the shells
+/// that can display one.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PossibleValueDef {
+    Value(String),
+    Detailed {
+        value: String,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        aliases: Vec<String>,
+        #[serde(default)]
+        hidden: bool,
+    },
+}
+
+impl PossibleValueDef {
+    fn value(&self) -> &str {
+        match self {
+            PossibleValueDef::Value(value) => value,
+            PossibleValueDef::Detailed { value, .. } => value,
+        }
+    }
+
+    fn description(&self) -> Option<&str> {
+        match self {
+            PossibleValueDef::Value(_) => None,
+            PossibleValueDef::Detailed { description, .. } => description.as_deref(),
+        }
+    }
+
+    fn is_hidden(&self) -> bool {
+        matches!(self, PossibleValueDef::Detailed { hidden: true, .. })
+    }
+
+    fn to_possible_value(&self) -> PossibleValue {
+        match self {
+            PossibleValueDef::Value(value) => PossibleValue::new(leak_string(value)),
+            PossibleValueDef::Detailed {
+                value,
+                description,
+                aliases,
+                hidden,
+            } => PossibleValue::new(leak_string(value))
+                .help(description.as_ref().map(leak_string))
+                .aliases(aliases.iter().map(leak_string))
+                .hide(*hidden),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct ArgumentDef {
     name: String,
     description: String,
     #[serde(default)]
     value_type: Option<String>,
     #[serde(default)]
-    possible_values: Vec<String>,
+    value_hint: Option<String>,
+    #[serde(default)]
+    possible_values: Vec<PossibleValueDef>,
     #[serde(default)]
     required: bool,
     #[serde(default)]
     global: bool,
+    #[serde(default)]
+    default_value: Option<String>,
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    hide: bool,
+    #[serde(default)]
+    deprecated: bool,
+    #[serde(default)]
+    long_help: Option<String>,
+    #[serde(default)]
+    conflicts_with: Vec<String>,
+    #[serde(default)]
+    requires: Vec<String>,
+    #[serde(default)]
+    required_unless_present: Vec<String>,
+    #[serde(default)]
+    num_args: Option<NumArgs>,
+    #[serde(default)]
+    value_delimiter: Option<char>,
+    #[serde(default)]
+    trailing_var_arg: bool,
+    #[serde(default)]
+    last: bool,
+    #[serde(default)]
+    allow_hyphen_values: bool,
+    /// Command computing the values at TAB time, see [`OptionDef::complete_command`].
+    #[serde(default)]
+    complete_command: Vec<String>,
+}
+
+impl ArgumentDef {
+    /// Whether the positional takes all remaining words, which command lines do unless they are
+    /// `last`.
+    fn is_trailing_var_arg(&self) -> bool {
+        self.trailing_var_arg || (!self.last && is_command_line(&self.value_hint, &self.value_type))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OptionAction {
+    /// Takes a single value, later occurrences override earlier ones.
+    #[default]
+    Set,
+    /// Takes a value on every occurrence.
+    Append,
+    /// Boolean switch that takes no value.
+    Flag,
+    /// Switch that takes no value and may be repeated (`-vvv`).
+    Count,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OptionDef {
-    name: String,
+    /// Internal id, referenced by relationships and groups.
+    #[serde(alias = "name")]
+    id: String,
+    #[serde(default)]
+    short: Option<char>,
+    /// Defaults to the id when neither `short` nor `long` is given.
+    #[serde(default)]
+    long: Option<String>,
     #[serde(default)]
-    short_names: Vec<char>,
+    visible_aliases: Vec<String>,
+    /// Also accepts the former `long_names`, which were hidden aliases.
+    #[serde(default, alias = "long_names")]
+    hidden_aliases: Vec<String>,
     #[serde(default)]
-    long_names: Vec<String>,
+    visible_short_aliases: Vec<char>,
+    /// Also accepts the former `short_names`, which were hidden aliases.
+    #[serde(default, alias = "short_names")]
+    hidden_short_aliases: Vec<char>,
     description: String,
     #[serde(default)]
+    action: OptionAction,
+    #[serde(default)]
     value_type: Option<String>,
     #[serde(default)]
-    possible_values: Vec<String>,
+    value_hint: Option<String>,
+    #[serde(default)]
+    possible_values: Vec<PossibleValueDef>,
     #[serde(default)]
     required: bool,
     #[serde(default)]
     global: bool,
+    #[serde(default)]
+    default_value: Option<String>,
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    hide: bool,
+    #[serde(default)]
+    deprecated: bool,
+    #[serde(default)]
+    long_help: Option<String>,
+    #[serde(default)]
+    conflicts_with: Vec<String>,
+    #[serde(default)]
+    requires: Vec<String>,
+    #[serde(default)]
+    required_unless_present: Vec<String>,
+    #[serde(default)]
+    num_args: Option<NumArgs>,
+    #[serde(default)]
+    value_delimiter: Option<char>,
+    #[serde(default)]
+    allow_hyphen_values: bool,
+    /// Command computing the values at TAB time, e.g. `["mytool", "__complete", "profiles"]`.
+    ///
+    /// The word being completed, possibly empty, is appended as the last argument. The command
+    /// prints one candidate per line, optionally followed by a tab and a description, and the shell
+    /// filters the candidates by what was typed. Bash drops the descriptions; PowerShell and Elvish
+    /// scripts do not complete values at all.
+    #[serde(default)]
+    complete_command: Vec<String>,
 }
 
+impl OptionDef {
+    fn long(&self) -> Option<&str> {
+        match (self.short, &self.long) {
+            (None, None) => Some(&self.id),
+            (_, long) => long.as_deref(),
+        }
+    }
+}
+
+/// A named set of arguments; by default at most one of them may be given.
 #[derive(Debug, Serialize, Deserialize)]
+struct GroupDef {
+    name: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    required: bool,
+    #[serde(default)]
+    multiple: bool,
+    #[serde(default)]
+    conflicts_with: Vec<String>,
+    #[serde(default)]
+    requires: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct CommandDef {
     name: String,
     #[serde(default)]
     description: String,
     #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    visible_aliases: Vec<String>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    subcommand_required: bool,
+    #[serde(default)]
+    args_conflicts_with_subcommands: bool,
+    #[serde(default)]
+    allow_external_subcommands: bool,
+    #[serde(default)]
     options: Vec<OptionDef>,
     #[serde(default)]
     subcommands: Vec<CommandDef>,
     #[serde(default)]
     arguments: Vec<ArgumentDef>,
+    #[serde(default)]
+    groups: Vec<GroupDef>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,22 +286,41 @@ struct CliDef {
 }
 
 fn leak_string<S: AsRef<str>>(s: S) -> &'static str {
-    return Box::leak(s.as_ref().to_string().into_boxed_str());
+    Box::leak(s.as_ref().to_string().into_boxed_str())
 }
 
-fn make_value_parser(settings: (&Vec<String>, &Option<String>)) -> ValueParser {
+/// The known `value_type`s, anything else is treated like `string`.
+const VALUE_TYPES: &[&str] = &[
+    "string",
+    "boolean",
+    "integer",
+    "float",
+    "file",
+    "dir",
+    "path",
+    "executable",
+    "command",
+    "command_line",
+    "username",
+    "hostname",
+    "url",
+    "email",
+];
+
+fn make_value_parser(settings: (&Vec<PossibleValueDef>, &Option<String>)) -> ValueParser {
     let (possible_values, value_type) = settings;
 
     if !possible_values.is_empty() {
-        return possible_values
-            .iter()
-            .map(|s| leak_string(s))
-            .collect::<Vec<_>>()
-            .into();
+        return PossibleValuesParser::new(
+            possible_values
+                .iter()
+                .map(PossibleValueDef::to_possible_value),
+        )
+        .into();
     }
 
     match value_type.as_deref() {
-        Some("file") | Some("dir") | Some("path") => value_parser!(PathBuf),
+        Some("file") | Some("dir") | Some("path") | Some("executable") => value_parser!(PathBuf),
         Some("boolean") => value_parser!(bool),
         Some("integer") => value_parser!(i64).into(),
         Some("float") => value_parser!(f64).into(),
@@ -81,44 +328,254 @@ fn make_value_parser(settings: (&Vec<String>, &Option<String>)) -> ValueParser {
     }
 }
 
-fn make_command(def: &CommandDef) -> Command {
+/// Help text for an argument, marking deprecated ones so the flag shows up as such in every shell.
+fn make_help(description: &str, deprecated: bool) -> &'static str {
+    if deprecated {
+        leak_string(format!("[deprecated] {description}"))
+    } else {
+        leak_string(description)
+    }
+}
+
+/// Parses a clap `ValueHint` name, e.g. `"dir_path"` or `"Hostname"`.
+fn parse_value_hint(hint: &str) -> Result<ValueHint, String> {
+    hint.replace(['_', '-'], "")
+        .parse()
+        .map_err(|e| format!("invalid value_hint '{hint}': {e}"))
+}
+
+/// Whether the completion hint is `CommandWithArguments`, which clap only allows on positionals
+/// taking multiple values that are trailing var args or `last`.
+fn is_command_line(value_hint: &Option<String>, value_type: &Option<String>) -> bool {
+    match value_hint {
+        Some(hint) => parse_value_hint(hint) == Ok(ValueHint::CommandWithArguments),
+        None => value_type.as_deref() == Some("command_line"),
+    }
+}
+
+/// Picks the completion hint from an explicit `value_hint` or derives it from `value_type`.
+fn make_value_hint(value_hint: &Option<String>, value_type: &Option<String>) -> ValueHint {
+    if let Some(hint) = value_hint {
+        return parse_value_hint(hint).expect("value_hint is checked by check_spec");
+    }
+
+    match value_type.as_deref() {
+        Some("file") => ValueHint::FilePath,
+        Some("dir") => ValueHint::DirPath,
+        Some("path") => ValueHint::AnyPath,
+        Some("executable") => ValueHint::ExecutablePath,
+        Some("command") => ValueHint::CommandName,
+        Some("command_line") => ValueHint::CommandWithArguments,
+        Some("username") => ValueHint::Username,
+        Some("hostname") => ValueHint::Hostname,
+        Some("url") => ValueHint::Url,
+        Some("email") => ValueHint::EmailAddress,
+        _ => ValueHint::Unknown,
+    }
+}
+
+/// Parses `num_args`, either an exact count or a range like `"1.."` or `"2..=5"`.
+fn try_value_range(num_args: &NumArgs) -> Result<ValueRange, String> {
+    let range = match num_args {
+        NumArgs::Exact(n) => return Ok(ValueRange::new(*n)),
+        NumArgs::Range(range) => range.trim(),
+    };
+
+    let invalid = || format!("invalid num_args range '{range}'");
+    let parse = |s: &str| -> Result<usize, String> { s.trim().parse().map_err(|_| invalid()) };
+
+    // Inclusive bounds, checked before building the range since clap asserts on them.
+    let (min, max) = match range.split_once("..") {
+        None => return Ok(ValueRange::new(parse(range)?)),
+        Some((min, max)) => {
+            let min = if min.is_empty() { 0 } else { parse(min)? };
+            let max = match max.strip_prefix('=') {
+                Some(max) => Some(parse(max)?),
+                None if max.is_empty() => None,
+                None => Some(parse(max)?.checked_sub(1).ok_or_else(invalid)?),
+            };
+            (min, max)
+        }
+    };
+    match max {
+        None => Ok(ValueRange::new(min..)),
+        Some(max) if min <= max => Ok(ValueRange::new(min..=max)),
+        Some(_) => Err(invalid()),
+    }
+}
+
+fn parse_value_range(num_args: &NumArgs) -> ValueRange {
+    try_value_range(num_args).expect("num_args is checked by check_spec")
+}
+
+fn make_value_range(num_args: Option<&NumArgs>) -> Resettable<ValueRange> {
+    match num_args {
+        Some(num_args) => parse_value_range(num_args).into(),
+        None => Resettable::Reset,
+    }
+}
+
+/// The number of values of the positional `arg_def`, defaulting to `1..` for trailing var args and
+/// command lines since clap requires them to take multiple values.
+fn make_positional_range(arg_def: &ArgumentDef) -> Resettable<ValueRange> {
+    let command_line = is_command_line(&arg_def.value_hint, &arg_def.value_type);
+    match &arg_def.num_args {
+        None if arg_def.trailing_var_arg || command_line => ValueRange::new(1..).into(),
+        num_args => make_value_range(num_args.as_ref()),
+    }
+}
+
+fn make_command(def: &CommandDef, hidden_globals: &[&str]) -> Command {
     let mut cmd = Command::new(leak_string(&def.name))
         .about(leak_string(&def.description))
         .disable_help_flag(true)
         .disable_version_flag(true)
-        .disable_help_subcommand(true);
-
-    for option in &def.options {
-        cmd = cmd.arg(
-            clap::Arg::new(leak_string(&option.name))
-                .long(leak_string(&option.name))
-                .short_aliases(option.short_names.iter().cloned())
-                .aliases(option.long_names.iter().map(|s| leak_string(s)))
-                .required(option.required)
-                .global(option.global)
-                .value_parser(make_value_parser((
-                    &option.possible_values,
-                    &option.value_type,
-                )))
-                .help(leak_string(&option.description)),
-        );
+        .disable_help_subcommand(true)
+        .aliases(def.aliases.iter().map(leak_string))
+        .visible_aliases(def.visible_aliases.iter().map(leak_string))
+        .subcommand_required(def.subcommand_required)
+        .args_conflicts_with_subcommands(def.args_conflicts_with_subcommands)
+        .allow_external_subcommands(def.allow_external_subcommands);
+
+    // clap_complete's generators don't honour `Arg::hide`, so hidden arguments are left out of the
+    // command entirely, along with any relationship pointing at them.
+    let mut hidden = hidden_globals.to_vec();
+    hidden.extend(def.options.iter().filter(|o| o.hide).map(|o| o.id.as_str()));
+    hidden.extend(
+        def.arguments
+            .iter()
+            .filter(|a| a.hide)
+            .map(|a| a.name.as_str()),
+    );
+    let visible = |names: &Vec<String>| -> Vec<&'static str> {
+        names
+            .iter()
+            .filter(|n| !hidden.contains(&n.as_str()))
+            .map(leak_string)
+            .collect()
+    };
+    // Members of exclusive groups conflict with each other explicitly, since the completion
+    // generators only look at per-argument conflicts.
+    let conflicts = |name: &str, names: &Vec<String>| -> Vec<&'static str> {
+        let mut candidates = visible(names);
+        for group in def.groups.iter().filter(|g| !g.multiple) {
+            if group.args.iter().any(|a| a == name) {
+                candidates.extend(visible(&group.args).into_iter().filter(|a| *a != name));
+            }
+        }
+        // Each conflict once, the shells would list it repeatedly otherwise.
+        let mut conflicts = Vec::new();
+        for arg in candidates {
+            if !conflicts.contains(&arg) {
+                conflicts.push(arg);
+            }
+        }
+        conflicts
+    };
+
+    for option in def.options.iter().filter(|o| !o.hide) {
+        let mut arg = clap::Arg::new(leak_string(&option.id))
+            .short(option.short)
+            .long(option.long().map(leak_string))
+            .visible_aliases(option.visible_aliases.iter().map(leak_string))
+            .aliases(option.hidden_aliases.iter().map(leak_string))
+            .visible_short_aliases(option.visible_short_aliases.iter().cloned())
+            .short_aliases(option.hidden_short_aliases.iter().cloned())
+            .required(option.required)
+            .global(option.global)
+            .env(option.env.as_ref().map(leak_string))
+            // Rendered texts are generated ahead of time and must not show this machine's values.
+            .hide_env_values(true)
+            .help(make_help(&option.description, option.deprecated))
+            .long_help(option.long_help.as_ref().map(leak_string))
+            .conflicts_with_all(conflicts(&option.id, &option.conflicts_with))
+            .required_unless_present_any(visible(&option.required_unless_present));
+
+        for required in visible(&option.requires) {
+            arg = arg.requires(required);
+        }
+
+        let value_parser = make_value_parser((&option.possible_values, &option.value_type));
+        arg = match option.action {
+            OptionAction::Flag => arg.action(clap::ArgAction::SetTrue),
+            OptionAction::Count => arg.action(clap::ArgAction::Count),
+            OptionAction::Set => arg.action(clap::ArgAction::Set).value_parser(value_parser),
+            OptionAction::Append => arg
+                .action(clap::ArgAction::Append)
+                .value_parser(value_parser),
+        };
+
+        if matches!(option.action, OptionAction::Set | OptionAction::Append) {
+            arg = arg
+                .default_value(option.default_value.as_ref().map(leak_string))
+                .value_hint(make_value_hint(&option.value_hint, &option.value_type))
+                .num_args(make_value_range(option.num_args.as_ref()))
+                .value_delimiter(option.value_delimiter)
+                .allow_hyphen_values(option.allow_hyphen_values);
+        }
+
+        cmd = cmd.arg(arg);
     }
 
-    for arg in &def.arguments {
-        cmd = cmd.arg(
-            clap::Arg::new(leak_string(&arg.name))
-                .value_parser(make_value_parser((&arg.possible_values, &arg.value_type)))
-                .required(arg.required)
-                .global(arg.global)
-                .help(leak_string(&arg.description)),
+    for arg_def in def.arguments.iter().filter(|a| !a.hide) {
+        let mut arg = clap::Arg::new(leak_string(&arg_def.name))
+            .value_parser(make_value_parser((
+                &arg_def.possible_values,
+                &arg_def.value_type,
+            )))
+            .value_hint(make_value_hint(&arg_def.value_hint, &arg_def.value_type))
+            .num_args(make_positional_range(arg_def))
+            .value_delimiter(arg_def.value_delimiter)
+            .trailing_var_arg(arg_def.is_trailing_var_arg())
+            .last(arg_def.last)
+            .allow_hyphen_values(arg_def.allow_hyphen_values)
+            .default_value(arg_def.default_value.as_ref().map(leak_string))
+            .env(arg_def.env.as_ref().map(leak_string))
+            .hide_env_values(true)
+            .required(arg_def.required)
+            .global(arg_def.global)
+            .help(make_help(&arg_def.description, arg_def.deprecated))
+            .long_help(arg_def.long_help.as_ref().map(leak_string))
+            .conflicts_with_all(conflicts(&arg_def.name, &arg_def.conflicts_with))
+            .required_unless_present_any(visible(&arg_def.required_unless_present));
+
+        for required in visible(&arg_def.requires) {
+            arg = arg.requires(required);
+        }
+
+        cmd = cmd.arg(arg);
+    }
+
+    for group in &def.groups {
+        cmd = cmd.group(
+            ArgGroup::new(leak_string(&group.name))
+                .args(visible(&group.args))
+                .required(group.required)
+                .multiple(group.multiple)
+                .conflicts_with_all(visible(&group.conflicts_with))
+                .requires_all(visible(&group.requires)),
         );
     }
 
-    for subcommand in &def.subcommands {
-        cmd = cmd.subcommand(make_command(&subcommand));
+    let mut hidden_globals = hidden_globals.to_vec();
+    hidden_globals.extend(
+        def.options
+            .iter()
+            .filter(|o| o.hide && o.global)
+            .map(|o| o.id.as_str()),
+    );
+    hidden_globals.extend(
+        def.arguments
+            .iter()
+            .filter(|a| a.hide && a.global)
+            .map(|a| a.name.as_str()),
+    );
+    // Like hidden arguments, hidden subcommands would still be completed, so they are dropped.
+    for subcommand in def.subcommands.iter().filter(|s| !s.hidden) {
+        cmd = cmd.subcommand(make_command(subcommand, &hidden_globals));
     }
 
-    return cmd;
+    cmd
 }
 
 fn make_cli() -> clap::Command {
@@ -130,96 +587,557 @@ fn make_cli() -> clap::Command {
                 .value_hint(clap::ValueHint::FilePath)
                 .required(true),
         )
+        .arg(
+            clap::Arg::new("input-format")
+                .long("input-format")
+                .value_parser(["json", "yaml", "toml"])
+                .ignore_case(true)
+                .help("Format of the input file, detected from its extension by default"),
+        )
         .arg(
             clap::Arg::new("output")
                 .long("output")
                 .short('o')
-                .value_hint(clap::ValueHint::FilePath)
-                .required(true),
+                .help("Output file, or directory for man pages and raw scripts for multiple shells")
+                .long_help(
+                    "Output file, or directory for man pages and raw scripts for multiple shells. \
+                     A single raw script and documentation are written to stdout when omitted or \
+                     `-`.",
+                )
+                .value_hint(clap::ValueHint::AnyPath)
+                .required_if_eq_any([("format", "c"), ("format", "man")]),
+        )
+        .arg(
+            clap::Arg::new("format")
+                .long("format")
+                .short('f')
+                .value_parser([
+                    "cpp",
+                    "cpp-parser",
+                    "cpp-complete",
+                    "c",
+                    "raw",
+                    "man",
+                    "markdown",
+                    "html",
+                    "fig",
+                    "carapace",
+                ])
+                .default_value("cpp")
+                .help("Emit a C++ header embedding all shells, C sources embedding all shells, a C++ argument parser, a C++ completer, the raw completion scripts, man pages, reference documentation or a completion spec")
+                .long_help(
+                    "Emit a C++ header embedding all shells (cpp), a C99 header and source file \
+                     embedding all shells (c, the source is written next to the header given as \
+                     output), a C++17 header parsing the command \
+                     line into typed structs (cpp-parser), a C++17 header answering \
+                     `COMPLETE=<shell> mytool` completion requests from the binary itself \
+                     (cpp-complete), the raw completion scripts (raw), \
+                     man pages (man), reference documentation (markdown, html), or a completion \
+                     spec for Fig/Amazon Q (fig) or Carapace (carapace).",
+                ),
+        )
+        .arg(
+            clap::Arg::new("embed-man")
+                .long("embed-man")
+                .action(clap::ArgAction::SetTrue)
+                .help("Also embed the man pages into the C++ header as `man_pages`, or the C sources as `clapper_man_page`"),
+        )
+        .arg(
+            clap::Arg::new("encoding")
+                .long("encoding")
+                .short('e')
+                .value_parser(value_parser!(Encoding))
+                .default_value("hex")
+                .help("How embedded files are encoded in the generated C++ header or C sources")
+                .long_help(
+                    "How embedded files are encoded in the generated C++ header or C sources: \
+                     byte by byte hex arrays (hex), a std::string map initialised from raw string \
+                     literals (raw-string, C++ only), constexpr std::string_view constants \
+                     (string-view, C++17 only), or C23 #embed directives referencing sidecar files \
+                     written next to the output (embed).",
+                ),
+        )
+        .arg(
+            clap::Arg::new("namespace")
+                .long("namespace")
+                .value_parser(NonEmptyStringValueParser::new())
+                .help("Namespace for the C++ outputs, may be nested like `a::b` [default: none for cpp, the binary name for cpp-parser and cpp-complete]"),
+        )
+        .arg(
+            clap::Arg::new("shells-macro")
+                .long("shells-macro")
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("SHELLS")
+                .help("Name of the macro listing the embedded shells in the C++ header"),
+        )
+        .arg(
+            clap::Arg::new("completions-name")
+                .long("completions-name")
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("shell_complete")
+                .help("Name of the completion script map in the C++ header"),
         )
-    // .arg(
-    //     clap::Arg::new("generator")
-    //         .long("generator")
-    //         .short('g')
-    //         .value_parser(value_parser!(Shell))
-    //         .ignore_case(true)
-    //         .default_value("bash"),
-    // )
-    // .args_conflicts_with_subcommands(true)
+        .arg(
+            clap::Arg::new("man-pages-name")
+                .long("man-pages-name")
+                .value_parser(NonEmptyStringValueParser::new())
+                .default_value("man_pages")
+                .help("Name of the man page map in the C++ header"),
+        )
+        .arg(
+            clap::Arg::new("include-guard")
+                .long("include-guard")
+                .value_parser(["pragma", "macro", "none"])
+                .help("Protect generated headers with `#pragma once`, an `#ifndef` macro guard or nothing [default: macro for c, pragma otherwise]"),
+        )
+        .arg(
+            clap::Arg::new("inline")
+                .long("inline")
+                .action(clap::ArgAction::SetTrue)
+                .help("Declare the C++ header's variables `inline` so translation units share one copy (C++17)"),
+        )
+        .arg(
+            clap::Arg::new("completions-subcommand")
+                .long("completions-subcommand")
+                .action(clap::ArgAction::SetTrue)
+                .help("Add a `completions <shell>` subcommand offering the embedded shells to the spec before generating"),
+        )
+        .arg(
+            clap::Arg::new("install-helper")
+                .long("install-helper")
+                .action(clap::ArgAction::SetTrue)
+                .help("Also generate `install_completions()` in the C++ header, which writes the script for the detected shell to its conventional location"),
+        )
+        .arg(
+            clap::Arg::new("check")
+                .long("check")
+                .action(clap::ArgAction::SetTrue)
+                .help("Only check the spec, reporting every problem with its JSON path")
+                .long_help(
+                    "Only check the spec and report every problem with its JSON path instead of \
+                     generating anything: duplicate ids, option names or subcommand names \
+                     (including inherited globals), unknown references, misplaced positional \
+                     and trailing var args, groups named like an argument, unknown value types, \
+                     possible values not matching the value type and empty descriptions. Exits \
+                     non-zero when anything was found; without --check only the errors stop the \
+                     generation.",
+                ),
+        )
+        .arg(
+            clap::Arg::new("strict")
+                .long("strict")
+                .action(clap::ArgAction::SetTrue)
+                .help("Reject fields the spec format does not know instead of ignoring them"),
+        )
+        .arg(
+            clap::Arg::new("message-format")
+                .long("message-format")
+                .value_parser(["human", "json"])
+                .default_value("human")
+                .help("Report errors in the spec as text or as one JSON object per error on stderr"),
+        )
+        .arg(
+            clap::Arg::new("shell")
+                .long("shell")
+                .short('s')
+                .visible_alias("generator")
+                .short_alias('g')
+                .value_parser(value_parser!(CompletionShell))
+                .ignore_case(true)
+                .action(clap::ArgAction::Append)
+                .required_if_eq("format", "raw")
+                .help("Shell to generate completions for, may be repeated [default for cpp and c: all]"),
+        )
+        .subcommand(
+            clap::Command::new("schema")
+                .about("Print the JSON Schema of the spec format, for editor completion and validation")
+                .arg(
+                    clap::Arg::new("strict")
+                        .long("strict")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Disallow unknown fields, like --strict does"),
+                )
+                .arg(
+                    clap::Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Output file [default: stdout]"),
+                ),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+}
+
+/// Adds the standard `completions <shell>` subcommand offering `shells`, so the generated scripts
+/// and documentation cover it like any other subcommand.
+fn add_completions_subcommand(
+    def: &mut CommandDef,
+    shells: &[CompletionShell],
+) -> Result<(), String> {
+    if def.subcommands.iter().any(|s| s.name == "completions") {
+        return Err("The spec already defines a `completions` subcommand".to_string());
+    }
+
+    def.subcommands.push(CommandDef {
+        name: "completions".to_string(),
+        description: "Print the shell completion script".to_string(),
+        arguments: vec![ArgumentDef {
+            name: "shell".to_string(),
+            description: "Shell to print the completion script for".to_string(),
+            possible_values: shells
+                .iter()
+                .map(|shell| PossibleValueDef::Value(shell.to_string()))
+                .collect(),
+            required: true,
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    Ok(())
 }
 
-fn main() {
-    let args = make_cli().get_matches();
+/// Deserializes the spec in `format`, falling back to the extension of `path` and finally JSON.
+fn parse_spec<T: DeserializeOwned>(
+    input: &str,
+    path: &Path,
+    format: Option<&str>,
+) -> Result<T, Error> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
 
-    let input = fs::read_to_string(args.get_one::<String>("input").unwrap())
-        .expect("Unable to read input file");
+    match format.or(extension.as_deref()) {
+        Some("yaml") | Some("yml") => {
+            serde_norway::from_str(input).map_err(|e| Error::yaml(path.into(), input, e))
+        }
+        Some("toml") => toml::from_str(input).map_err(|e| Error::toml(path.into(), input, e)),
+        _ => serde_json::from_str(input).map_err(|e| Error::json(path.into(), input, e)),
+    }
+}
 
-    let command_def: CliDef = serde_json::from_str(&input).expect("Unable to parse input JSON");
-    let command = make_command(&command_def.command);
+fn generate_completion(generator: impl Generator, command: &Command) -> Vec<u8> {
+    let mut buf = BufWriter::new(Vec::new());
     let binary_name = command.get_name().to_string();
+    clap_complete::generate(generator, &mut command.clone(), binary_name, &mut buf);
+    buf.flush().unwrap();
+    buf.into_inner().unwrap()
+}
 
-    let mut cpp_source = BufWriter::new(Vec::new());
-    writeln!(cpp_source, "#include <string>").unwrap();
-    writeln!(cpp_source, "#include <vector>").unwrap();
-    writeln!(cpp_source, "#include <cstdint>").unwrap();
-    writeln!(cpp_source, "#include <map>").unwrap();
-
-    writeln!(cpp_source).unwrap();
-    let shells = [
-        Shell::Bash,
-        Shell::Zsh,
-        Shell::Fish,
-        Shell::PowerShell,
-        Shell::Elvish,
-    ];
-
-    writeln!(
-        cpp_source,
-        "#define SHELLS {}",
-        shells
-            .iter()
-            .map(|s| format!("\"{}\"", s.to_string()))
-            .collect::<Vec<_>>()
-            .join(",")
-    )
-    .unwrap();
-
-    writeln!(
-        cpp_source,
-        "const std::map<std::string, std::vector<std::uint8_t>> shell_complete = {{"
-    )
-    .unwrap();
-
-    for shell in shells {
-        let mut buf = BufWriter::new(Vec::new());
-        clap_complete::generate(shell, &mut command.clone(), binary_name.clone(), &mut buf);
-        buf.flush().unwrap();
-        let buf = buf.into_inner().unwrap();
-
-        writeln!(cpp_source, "{{ \"{}\", {{", shell.to_string()).unwrap();
-
-        for (i, byte) in buf.iter().enumerate() {
-            if i % 12 == 0 {
-                write!(cpp_source, "    ").unwrap();
+/// Generates the completion script for `shell`, wiring up dynamic completions.
+fn generate_script(shell: CompletionShell, command: &Command) -> Result<Vec<u8>, Error> {
+    let script = String::from_utf8(generate_completion(shell, command)).unwrap();
+    dynamic::resolve_script(shell, command, script)
+        .map(String::into_bytes)
+        .map_err(Error::Generate)
+}
+
+/// Writes `content` to `output`, or to stdout when it is omitted or `-`.
+fn write_output(output: Option<&String>, content: &[u8]) -> Result<(), Error> {
+    match output.filter(|o| o.as_str() != "-") {
+        Some(path) => write_file(Path::new(path), content),
+        None => std::io::stdout()
+            .write_all(content)
+            .map_err(Error::io("<stdout>", "write")),
+    }
+}
+
+fn write_file(path: &Path, content: impl AsRef<[u8]>) -> Result<(), Error> {
+    fs::write(path, content).map_err(Error::io(path, "write"))
+}
+
+fn create_dir(path: &Path) -> Result<(), Error> {
+    fs::create_dir_all(path).map_err(Error::io(path, "create"))
+}
+
+/// Writes the plain completion scripts for `shells`.
+///
+/// A single shell is written to stdout without an output path (or with `-`), otherwise to the given
+/// file unless it names an existing directory. Multiple shells are always written into the
+/// directory, using the conventional file name for each shell (`_mytool`, `mytool.fish`, ...).
+fn write_raw_scripts(
+    command: &Command,
+    shells: &[CompletionShell],
+    output: Option<&String>,
+) -> Result<(), Error> {
+    let binary_name = command.get_name().to_string();
+
+    match output.filter(|o| o.as_str() != "-").map(PathBuf::from) {
+        None if shells.len() > 1 => {
+            return Err(Error::Usage(
+                "--output must name a directory when generating raw scripts for multiple shells"
+                    .to_string(),
+            ));
+        }
+        None => write_output(None, &generate_script(shells[0], command)?)?,
+        Some(path) if shells.len() == 1 && !path.is_dir() => {
+            write_file(&path, generate_script(shells[0], command)?)?;
+        }
+        Some(dir) => {
+            create_dir(&dir)?;
+            for shell in shells {
+                write_file(
+                    &dir.join(shell.file_name(&binary_name)),
+                    generate_script(*shell, command)?,
+                )?;
             }
-            write!(cpp_source, "0x{:02X}, ", byte).unwrap();
-            if i % 12 == 11 || i == buf.len() - 1 {
-                writeln!(cpp_source).unwrap();
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &clap::ArgMatches) -> Result<(), Error> {
+    if let Some(("schema", schema_args)) = args.subcommand() {
+        let schema = schema::generate_schema(schema_args.get_flag("strict"));
+        let schema = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+        return write_output(schema_args.get_one::<String>("output"), schema.as_bytes());
+    }
+
+    let input_path = Path::new(args.get_one::<String>("input").unwrap());
+    let input = fs::read_to_string(input_path).map_err(Error::io(input_path, "read"))?;
+
+    let input_format = args
+        .get_one::<String>("input-format")
+        .map(|f| f.to_ascii_lowercase());
+    let mut command_def: CliDef = parse_spec(&input, input_path, input_format.as_deref())?;
+    let spec_error = |problems| Error::Spec {
+        path: input_path.to_path_buf(),
+        problems,
+    };
+    let output = args.get_one::<String>("output");
+    let mut shells = Vec::new();
+    // Each shell once, in the order given; repeating one would define its script twice.
+    for shell in args
+        .get_many::<CompletionShell>("shell")
+        .map(|shells| shells.cloned().collect::<Vec<_>>())
+        .unwrap_or_else(|| CompletionShell::ALL.to_vec())
+    {
+        if !shells.contains(&shell) {
+            shells.push(shell);
+        }
+    }
+    if args.get_flag("completions-subcommand") {
+        add_completions_subcommand(&mut command_def.command, &shells).map_err(|message| {
+            spec_error(vec![Problem {
+                severity: Severity::Error,
+                path: "$.command.subcommands".to_string(),
+                message,
+            }])
+        })?;
+    }
+    let mut problems = check::check_spec(&command_def.command);
+    if args.get_flag("strict") {
+        let document = parse_spec(&input, input_path, input_format.as_deref())?;
+        let mut unknown = schema::unknown_fields(&document);
+        unknown.append(&mut problems);
+        problems = unknown;
+    }
+    if args.get_flag("check") {
+        if problems.is_empty() {
+            return Ok(());
+        }
+        return Err(spec_error(problems));
+    }
+    // Warnings are only reported by `--check`.
+    problems.retain(|p| p.severity == Severity::Error);
+    if !problems.is_empty() {
+        return Err(spec_error(problems));
+    }
+    let command = make_command(&command_def.command, &[]);
+    // Completion outputs get placeholders for dynamic values, the other outputs must not show them.
+    let completion_command = dynamic::add_placeholders(command.clone(), &command_def.command);
+
+    let encoding = *args.get_one::<Encoding>("encoding").unwrap();
+    let namespace = args.get_one::<String>("namespace").map(String::as_str);
+    let include_guard_kind = args.get_one::<String>("include-guard").map(String::as_str);
+
+    let format = args.get_one::<String>("format").unwrap().as_str();
+    // clap does not enforce `required_if_eq` for default values, so the default cpp format checks
+    // for `--output` here.
+    let output_path = || {
+        output
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::Usage(format!("--output is required for the {format} format")))
+    };
+    // Headers are named after their file name, which paths like `..` do not have.
+    let header_path = || {
+        let path = output_path()?;
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some(_) => Ok(path),
+            None => Err(Error::Usage(format!(
+                "--output must name a file for the {format} format, got '{}'",
+                path.display()
+            ))),
+        }
+    };
+
+    match format {
+        "raw" => write_raw_scripts(&completion_command, &shells, output)?,
+        "man" => {
+            let dir = output_path()?;
+            create_dir(&dir)?;
+            for (file_name, page) in man::generate_man_pages(&command) {
+                write_file(&dir.join(file_name), page)?;
+            }
+        }
+        format @ ("markdown" | "html") => {
+            let docs = docs::generate_docs(&command_def.command, &command, format == "html");
+            write_output(output, docs.as_bytes())?;
+        }
+        "fig" => {
+            let spec = String::from_utf8(generate_completion(
+                clap_complete_fig::Fig,
+                &completion_command,
+            ))
+            .unwrap();
+            let spec = dynamic::resolve_fig(spec).map_err(Error::Generate)?;
+            write_output(output, spec.as_bytes())?;
+        }
+        "c" => {
+            if !matches!(encoding, Encoding::Hex | Encoding::Embed) {
+                return Err(Error::Usage(
+                    "The C format only supports the hex and embed encodings".to_string(),
+                ));
+            }
+            let header_path = header_path()?;
+            if header_path.extension().is_some_and(|e| e == "c") {
+                return Err(Error::Usage(
+                    "Output for the C format must name the header, the source is written next to it"
+                        .to_string(),
+                ));
             }
+            let (header, source, sidecars) = c_header::generate_c_sources(
+                &completion_command,
+                &shells,
+                args.get_flag("embed-man"),
+                encoding,
+                include_guard_kind.unwrap_or("macro"),
+                &header_path,
+            )?;
+            write_file(&header_path, header)?;
+            write_file(&header_path.with_extension("c"), source)?;
+            for (path, content) in sidecars {
+                write_file(&path, content)?;
+            }
+        }
+        "cpp-parser" => {
+            let parser = cpp_parser::generate_cpp_parser(
+                &command_def.command,
+                &command,
+                namespace,
+                include_guard_kind.unwrap_or("pragma"),
+                output.filter(|o| o.as_str() != "-").map(Path::new),
+            );
+            write_output(output, parser.as_bytes())?;
+        }
+        "cpp-complete" => {
+            let completer = cpp_complete::generate_cpp_completer(
+                &command_def.command,
+                namespace,
+                include_guard_kind.unwrap_or("pragma"),
+                output.filter(|o| o.as_str() != "-").map(Path::new),
+            );
+            write_output(output, completer.as_bytes())?;
+        }
+        "carapace" => {
+            write_output(
+                output,
+                carapace::generate_carapace_spec(&completion_command).as_bytes(),
+            )?;
+        }
+        _ => {
+            let header_path = header_path()?;
+            let options = CppHeaderOptions {
+                embed_man: args.get_flag("embed-man"),
+                encoding,
+                namespace,
+                shells_macro: args.get_one::<String>("shells-macro").unwrap(),
+                completions_name: args.get_one::<String>("completions-name").unwrap(),
+                man_pages_name: args.get_one::<String>("man-pages-name").unwrap(),
+                include_guard: include_guard_kind.unwrap_or("pragma"),
+                inline: args.get_flag("inline"),
+                install_helper: args.get_flag("install-helper"),
+            };
+            let (header, sidecars) = cpp_header::generate_cpp_header(
+                &completion_command,
+                &shells,
+                &options,
+                &header_path,
+            )?;
+            write_file(&header_path, header)?;
+            for (path, content) in sidecars {
+                write_file(&path, content)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the raw command line asks for JSON messages, for errors clap reports before the
+/// arguments are parsed.
+fn json_requested() -> bool {
+    let args = std::env::args_os().collect::<Vec<_>>();
+    args.iter().any(|a| a == "--message-format=json")
+        || args
+            .windows(2)
+            .any(|w| w[0] == "--message-format" && w[1] == "json")
+}
+
+fn main() -> ExitCode {
+    let args = match make_cli().try_get_matches() {
+        Ok(args) => args,
+        Err(error) if error.use_stderr() && json_requested() => {
+            // Only the message itself, without the usage and `--help` hint following it.
+            let rendered = error.to_string();
+            let message = rendered.strip_prefix("error: ").unwrap_or(&rendered);
+            let message = message.split("\n\n").next().unwrap_or_default();
+            Error::Usage(message.trim_end().to_string()).report(true);
+            return ExitCode::from(error.exit_code() as u8);
+        }
+        Err(error) => error.exit(),
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error.report(args.get_one::<String>("message-format").unwrap() == "json");
+            ExitCode::FAILURE
         }
-        writeln!(cpp_source, "}}}},").unwrap();
     }
-    writeln!(cpp_source, "}};").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    cpp_source.flush().unwrap();
-    let cpp_source = cpp_source.into_inner().unwrap();
+    fn range(num_args: &str) -> Result<(usize, usize), String> {
+        try_value_range(&NumArgs::Range(num_args.to_string()))
+            .map(|range| (range.min_values(), range.max_values()))
+    }
 
-    std::fs::write(args.get_one::<String>("output").unwrap(), cpp_source).unwrap();
+    #[test]
+    fn value_range_bounds() {
+        assert_eq!(range("1.."), Ok((1, usize::MAX)));
+        assert_eq!(range("..=3"), Ok((0, 3)));
+        assert_eq!(range("..3"), Ok((0, 2)));
+        assert_eq!(range(" 2 ..= 5 "), Ok((2, 5)));
+        assert_eq!(range("4"), Ok((4, 4)));
+        let exact = try_value_range(&NumArgs::Exact(2)).unwrap();
+        assert_eq!((exact.min_values(), exact.max_values()), (2, 2));
+    }
 
-    // clap_complete::generate(
-    //     args.get_one::<Shell>("generator").unwrap().clone(),
-    //     &mut command,
-    //     binary_name,
-    //     &mut output,
-    // );
+    #[test]
+    fn value_range_rejects_empty_ranges() {
+        assert_eq!(
+            range("2..1"),
+            Err("invalid num_args range '2..1'".to_string())
+        );
+        assert_eq!(
+            range("..0"),
+            Err("invalid num_args range '..0'".to_string())
+        );
+        assert!(range("3..=1").is_err());
+        assert!(range("a..").is_err());
+    }
 }
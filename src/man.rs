@@ -0,0 +1,26 @@
+use crate::dynamic;
+use clap::Command;
+use clap_mangen::Man;
+
+/// Renders a man page for `command` and every visible subcommand, keyed by file name
+/// (`mytool.1`, `mytool-sub.1`, `mytool-sub-nested.1`, ...).
+pub fn generate_man_pages(command: &Command) -> Vec<(String, Vec<u8>)> {
+    fn generate(cmd: &Command, pages: &mut Vec<(String, Vec<u8>)>) {
+        let man = Man::new(cmd.clone());
+        let mut buf = Vec::new();
+        man.render(&mut buf).unwrap();
+        pages.push((man.get_filename(), buf));
+
+        for subcommand in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+            generate(subcommand, pages);
+        }
+    }
+
+    // Building the command assigns the `mytool-sub` display names used for the file names.
+    let mut cmd = dynamic::remove_placeholders(command.clone());
+    cmd.build();
+
+    let mut pages = Vec::new();
+    generate(&cmd, &mut pages);
+    pages
+}
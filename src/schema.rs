@@ -0,0 +1,394 @@
+use crate::{
+    VALUE_TYPES,
+    check::{Problem, Severity},
+};
+use serde_json::{Map, Value, json};
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "default": false, "description": description })
+}
+
+fn strings(description: &str) -> Value {
+    json!({ "type": "array", "items": { "type": "string" }, "description": description })
+}
+
+fn character() -> Value {
+    json!({ "type": "string", "minLength": 1, "maxLength": 1 })
+}
+
+/// The properties shared by options and positional arguments.
+fn value_properties() -> Map<String, Value> {
+    let properties = json!({
+        "description": string("Help text"),
+        "long_help": string("Longer help text shown by `--help` and in the man pages"),
+        "value_type": {
+            "enum": VALUE_TYPES,
+            "description": "Type of the value, used for parsing and completion",
+        },
+        "value_hint": string(
+            "Completion hint overriding the one derived from value_type, any clap ValueHint name \
+             like `dir_path` or `Hostname`",
+        ),
+        "possible_values": {
+            "type": "array",
+            "items": { "$ref": "#/$defs/possible_value" },
+        },
+        "required": boolean("Must be given"),
+        "global": boolean("Also accepted by all subcommands"),
+        "default_value": string("Value used when not given"),
+        "env": string("Environment variable read when not given"),
+        "hide": boolean("Hidden from help and completions"),
+        "deprecated": boolean("Marked as deprecated in help and completions"),
+        "conflicts_with": strings("Ids of arguments or groups that may not be given with this one"),
+        "requires": strings("Ids of arguments or groups that must be given with this one"),
+        "required_unless_present": strings("Ids of arguments that make this one optional"),
+        "num_args": { "$ref": "#/$defs/num_args" },
+        "value_delimiter": character(),
+        "allow_hyphen_values": boolean("Values may start with `-`"),
+        "complete_command": strings(
+            "Command printing the values at TAB time, the current word is appended",
+        ),
+    });
+    fields(properties)
+}
+
+/// The fields of the JSON object `value`.
+fn fields(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(fields) => fields,
+        _ => unreachable!("not an object"),
+    }
+}
+
+fn object(properties: Map<String, Value>, required: &[&str], strict: bool) -> Value {
+    let mut object = json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    });
+    if strict {
+        object["additionalProperties"] = json!(false);
+    }
+    object
+}
+
+/// The JSON Schema of the spec format, disallowing unknown fields when `strict` is set.
+pub fn generate_schema(strict: bool) -> Value {
+    let mut option = value_properties();
+    option.extend(fields(json!({
+        "id": string("Internal id, referenced by relationships and groups"),
+        "name": string("Alias of id"),
+        "short": character(),
+        "long": string("Long name, defaults to the id when neither short nor long is given"),
+        "visible_aliases": strings("Long aliases shown in help"),
+        "hidden_aliases": strings("Long aliases not shown in help"),
+        "visible_short_aliases": { "type": "array", "items": character() },
+        "hidden_short_aliases": { "type": "array", "items": character() },
+        "long_names": strings("Deprecated alias of hidden_aliases"),
+        "short_names": {
+            "type": "array",
+            "items": character(),
+            "description": "Deprecated alias of hidden_short_aliases",
+        },
+        "action": {
+            "enum": ["set", "append", "flag", "count"],
+            "default": "set",
+            "description": "set takes a value, append one per occurrence, flag and count none",
+        },
+    })));
+    let mut option = object(option, &["description"], strict);
+    option["oneOf"] = json!([{ "required": ["id"] }, { "required": ["name"] }]);
+
+    let mut argument = value_properties();
+    argument.extend(fields(json!({
+        "name": string("Name, also used as id"),
+        "trailing_var_arg": boolean(
+            "Takes all remaining words, even those starting with `-`; num_args defaults to `1..`",
+        ),
+        "last": boolean("Only given after `--`"),
+    })));
+    let argument = object(argument, &["name", "description"], strict);
+
+    let group = object(
+        fields(json!({
+            "name": string("Name, referenced like an argument id"),
+            "args": strings("Ids of the arguments in the group"),
+            "required": boolean("One of the arguments must be given"),
+            "multiple": boolean("More than one of the arguments may be given"),
+            "conflicts_with": strings("Ids of arguments or groups excluded by the group"),
+            "requires": strings("Ids of arguments or groups that must be given with the group"),
+        })),
+        &["name"],
+        strict,
+    );
+
+    let command = object(
+        fields(json!({
+            "name": string("Name of the binary or subcommand"),
+            "description": string("Help text"),
+            "aliases": strings("Hidden aliases"),
+            "visible_aliases": strings("Aliases shown in help"),
+            "hidden": boolean("Hidden from help and completions"),
+            "subcommand_required": boolean("A subcommand must be given"),
+            "args_conflicts_with_subcommands": boolean("No arguments with a subcommand"),
+            "allow_external_subcommands": boolean("Unknown subcommands are accepted"),
+            "options": { "type": "array", "items": { "$ref": "#/$defs/option" } },
+            "subcommands": { "type": "array", "items": { "$ref": "#/$defs/command" } },
+            "arguments": { "type": "array", "items": { "$ref": "#/$defs/argument" } },
+            "groups": { "type": "array", "items": { "$ref": "#/$defs/group" } },
+        })),
+        &["name"],
+        strict,
+    );
+
+    let possible_value = json!({
+        "oneOf": [
+            { "type": "string" },
+            object(
+                fields(json!({
+                    "value": string("The value"),
+                    "description": string("Help text shown by the shells that support it"),
+                    "aliases": strings("Other accepted spellings"),
+                    "hidden": boolean("Accepted but not completed"),
+                })),
+                &["value"],
+                strict,
+            ),
+        ],
+    });
+
+    let mut schema = object(
+        fields(json!({
+            "$schema": string("JSON Schema of the spec, for editors; ignored by clapper"),
+            "command": { "$ref": "#/$defs/command" },
+        })),
+        &["command"],
+        strict,
+    );
+    schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+    schema["title"] = json!("clapper spec");
+    schema["$defs"] = json!({
+        "command": command,
+        "option": option,
+        "argument": argument,
+        "group": group,
+        "possible_value": possible_value,
+        "num_args": {
+            "oneOf": [
+                { "type": "integer", "minimum": 0 },
+                { "type": "string", "pattern": "^\\s*(\\d+|\\d*\\s*\\.\\.=?\\s*\\d*)\\s*$" },
+            ],
+            "description": "Number of values, exact (`2`) or a range (`\"1..\"`, `\"2..=5\"`)",
+        },
+    });
+    schema
+}
+
+/// The known field closest to `field`, when it looks like a typo of it.
+fn suggestion<'a>(field: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    let distance = |a: &str, b: &str| {
+        let b = b.chars().collect::<Vec<_>>();
+        let mut row = (0..=b.len()).collect::<Vec<_>>();
+        for (i, ca) in a.chars().enumerate() {
+            let mut previous = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let current = row[j + 1];
+                row[j + 1] = (previous + usize::from(ca != *cb))
+                    .min(row[j] + 1)
+                    .min(current + 1);
+                previous = current;
+            }
+        }
+        row[b.len()]
+    };
+    known
+        .map(|k| (distance(field, k), k))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k)
+}
+
+fn walk(schema: &Value, defs: &Value, value: &Value, path: &str, problems: &mut Vec<Problem>) {
+    if let Some(name) = schema["$ref"].as_str() {
+        let name = name.trim_start_matches("#/$defs/");
+        return walk(&defs[name], defs, value, path, problems);
+    }
+    if let Some(branches) = schema["oneOf"].as_array() {
+        // Only objects have fields, so the object branch is the one to follow.
+        let branch = branches.iter().find(|b| b["type"] == "object");
+        if let (Some(branch), Value::Object(_)) = (branch, value) {
+            return walk(branch, defs, value, path, problems);
+        }
+    }
+
+    match value {
+        Value::Object(fields) => {
+            let Some(properties) = schema["properties"].as_object() else {
+                return;
+            };
+            for (field, value) in fields {
+                let path = format!("{path}.{field}");
+                match properties.get(field) {
+                    Some(property) => walk(property, defs, value, &path, problems),
+                    None => {
+                        let hint = suggestion(field, properties.keys())
+                            .map(|s| format!(", did you mean '{s}'?"))
+                            .unwrap_or_default();
+                        problems.push(Problem {
+                            severity: Severity::Error,
+                            path,
+                            message: format!("unknown field '{field}'{hint}"),
+                        });
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                walk(
+                    &schema["items"],
+                    defs,
+                    item,
+                    &format!("{path}[{i}]"),
+                    problems,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reports every field of `document` that the spec format does not know, which serde would
+/// otherwise silently ignore.
+pub fn unknown_fields(document: &Value) -> Vec<Problem> {
+    let schema = generate_schema(true);
+    let mut problems = Vec::new();
+    walk(&schema, &schema["$defs"], document, "$", &mut problems);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_spec;
+    use std::path::Path;
+
+    fn unknown(input: &str, file: &str) -> Vec<(String, String)> {
+        let document = parse_spec::<Value>(input, Path::new(file), None).unwrap();
+        unknown_fields(&document)
+            .into_iter()
+            .map(|p| (p.path, p.message))
+            .collect()
+    }
+
+    fn problem(path: &str, message: &str) -> (String, String) {
+        (path.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn suggests_close_fields() {
+        let known = ["description", "default_value", "deprecated"].map(String::from);
+        assert_eq!(
+            suggestion("descripton", known.iter()).map(String::as_str),
+            Some("description")
+        );
+        assert_eq!(
+            suggestion("deprecate", known.iter()).map(String::as_str),
+            Some("deprecated")
+        );
+        assert_eq!(suggestion("colour", known.iter()), None);
+    }
+
+    #[test]
+    fn known_fields_pass() {
+        let input = r#"{"command": {"name": "t", "options": [
+            {"id": "v", "description": "v", "possible_values": ["a", {"value": "b", "hidden": true}]},
+            {"name": "old", "description": "o", "short_names": ["o"], "long_names": ["older"]}
+        ]}}"#;
+        assert!(unknown(input, "spec.json").is_empty());
+    }
+
+    #[test]
+    fn schema_reference_passes() {
+        let input = r#"{"$schema": "./clapper.schema.json", "command": {"name": "t"}}"#;
+        assert!(unknown(input, "spec.json").is_empty());
+        let input = "# yaml-language-server: $schema=clapper.schema.json\n\
+            $schema: clapper.schema.json\ncommand:\n  name: t\n";
+        assert!(unknown(input, "spec.yaml").is_empty());
+        assert!(generate_schema(true)["properties"]["$schema"].is_object());
+    }
+
+    #[test]
+    fn json_unknown_fields() {
+        let input = r#"{"command": {"name": "t", "colour": true, "subcommands": [
+            {"name": "s", "options": [{"id": "v", "descripton": "v"}]}
+        ]}}"#;
+        assert_eq!(
+            unknown(input, "spec.json"),
+            [
+                problem("$.command.colour", "unknown field 'colour'"),
+                problem(
+                    "$.command.subcommands[0].options[0].descripton",
+                    "unknown field 'descripton', did you mean 'description'?"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn yaml_unknown_fields() {
+        let input = "\
+command:
+  name: t
+  arguments:
+    - name: file
+      description: f
+      requird: true
+      possible_values:
+        - value: a
+          hiden: true
+";
+        assert_eq!(
+            unknown(input, "spec.yaml"),
+            [
+                problem(
+                    "$.command.arguments[0].possible_values[0].hiden",
+                    "unknown field 'hiden', did you mean 'hidden'?"
+                ),
+                problem(
+                    "$.command.arguments[0].requird",
+                    "unknown field 'requird', did you mean 'required'?"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_unknown_fields() {
+        let input = r#"
+[command]
+name = "t"
+subcomands = []
+
+[[command.groups]]
+name = "g"
+args = ["a"]
+multi = true
+"#;
+        assert_eq!(
+            unknown(input, "spec.toml"),
+            [
+                problem("$.command.groups[0].multi", "unknown field 'multi'"),
+                problem(
+                    "$.command.subcomands",
+                    "unknown field 'subcomands', did you mean 'subcommands'?"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use clap::{ValueEnum, builder::PossibleValue};
+use clap_complete::{Generator, Shell};
+use clap_complete_nushell::Nushell;
+use std::{fmt, io::Write};
+
+/// Every shell clapper can generate completions for: the ones built into `clap_complete` plus
+/// Nushell, which lives in its own crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Elvish,
+    Nushell,
+}
+
+impl CompletionShell {
+    pub const ALL: [CompletionShell; 6] = [
+        CompletionShell::Bash,
+        CompletionShell::Zsh,
+        CompletionShell::Fish,
+        CompletionShell::PowerShell,
+        CompletionShell::Elvish,
+        CompletionShell::Nushell,
+    ];
+
+    fn builtin(self) -> Option<Shell> {
+        match self {
+            CompletionShell::Bash => Some(Shell::Bash),
+            CompletionShell::Zsh => Some(Shell::Zsh),
+            CompletionShell::Fish => Some(Shell::Fish),
+            CompletionShell::PowerShell => Some(Shell::PowerShell),
+            CompletionShell::Elvish => Some(Shell::Elvish),
+            CompletionShell::Nushell => None,
+        }
+    }
+}
+
+impl fmt::Display for CompletionShell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.builtin() {
+            Some(shell) => shell.fmt(f),
+            None => f.write_str("nushell"),
+        }
+    }
+}
+
+impl ValueEnum for CompletionShell {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Self::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self.builtin() {
+            Some(shell) => shell.to_possible_value(),
+            None => Some(PossibleValue::new("nushell").alias("nu")),
+        }
+    }
+}
+
+impl Generator for CompletionShell {
+    fn file_name(&self, name: &str) -> String {
+        match self.builtin() {
+            Some(shell) => shell.file_name(name),
+            None => Nushell.file_name(name),
+        }
+    }
+
+    fn generate(&self, cmd: &clap::Command, buf: &mut dyn Write) {
+        match self.builtin() {
+            Some(shell) => shell.generate(cmd, buf),
+            None => Nushell.generate(cmd, buf),
+        }
+    }
+}